@@ -0,0 +1,327 @@
+use super::{
+    acme_error,
+    jws::{b64, AccountKey, KeyId},
+    Challenges,
+};
+use crate::{error::AppError, Result};
+use actix_http::{encoding::Decoder, Payload, PayloadStream};
+use actix_rt::time::delay_for;
+use awc::{http::header, Client, ClientResponse, Connector};
+use openssl::{
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    ssl::{SslConnector, SslMethod},
+    stack::Stack,
+    x509::{extension::SubjectAlternativeName, X509NameBuilder, X509ReqBuilder},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+/// Responses are transparently decompressed
+type Response = ClientResponse<Decoder<Payload<PayloadStream>>>;
+
+/// How often, and how many times, to poll pending authorizations and orders
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: usize = 30;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: String,
+}
+
+/// A certificate issued by the ACME server
+pub struct Issued {
+    /// Private key the certificate was issued for
+    pub key: PKey<Private>,
+    /// PEM encoded certificate chain, leaf first
+    pub chain_pem: Vec<u8>,
+}
+
+/// Minimal ACME v2 (RFC 8555) client, only what is needed for HTTP-01
+pub struct AcmeClient {
+    http: Client,
+    directory: Directory,
+    key: AccountKey,
+    account: String,
+    nonce: Option<String>,
+}
+
+impl AcmeClient {
+    /// Fetch the directory and register (or look up) the account for `key`.
+    /// `ca_file` adds a trusted root for the ACME server itself, e.g. Pebble's test CA.
+    pub async fn connect(
+        directory_url: &str,
+        ca_file: Option<&str>,
+        key: AccountKey,
+        email: &str,
+    ) -> Result<Self> {
+        let mut ssl = SslConnector::builder(SslMethod::tls())?;
+        if let Some(ca_file) = ca_file {
+            ssl.set_ca_file(ca_file)?;
+        }
+        let http = Client::build()
+            .connector(Connector::new().ssl(ssl.build()).finish())
+            .timeout(Duration::from_secs(30))
+            .finish();
+
+        let mut res = http.get(directory_url).send().await.map_err(acme_error)?;
+        let directory: Directory = read_json(&mut res).await?;
+
+        let mut client = AcmeClient {
+            http,
+            directory,
+            key,
+            account: String::new(),
+            nonce: None,
+        };
+
+        let new_account = client.directory.new_account.clone();
+        let payload = json!({
+            "termsOfServiceAgreed": true,
+            "contact": [format!("mailto:{}", email)],
+        });
+        let res = client.post(&new_account, Some(&payload)).await?;
+        client.account = location(&res)?;
+
+        Ok(client)
+    }
+
+    /// Run a full order for `domain`: answer the HTTP-01 challenges through
+    /// `challenges`, finalize with a new key and download the chain.
+    pub async fn issue(&mut self, domain: &str, challenges: &Challenges) -> Result<Issued> {
+        let new_order = self.directory.new_order.clone();
+        let payload = json!({ "identifiers": [{ "type": "dns", "value": domain }] });
+        let mut res = self.post(&new_order, Some(&payload)).await?;
+        let order_url = location(&res)?;
+        let order: Order = read_json(&mut res).await?;
+
+        for authz_url in &order.authorizations {
+            self.authorize(authz_url, challenges).await?;
+        }
+
+        let (key, csr) = csr(domain)?;
+        let payload = json!({ "csr": b64(&csr) });
+        self.post(&order.finalize, Some(&payload)).await?;
+
+        let order: Order = self
+            .poll(&order_url, |o: &Order| {
+                o.status != "processing" && o.status != "pending"
+            })
+            .await?;
+        let certificate = match (order.status.as_str(), order.certificate) {
+            ("valid", Some(url)) => url,
+            (status, _) => {
+                return Err(AppError::AcmeError(format!(
+                    "order for {} ended as '{}'",
+                    domain, status
+                )))
+            }
+        };
+
+        let mut res = self.post::<()>(&certificate, None).await?;
+        let chain_pem = res.body().limit(1 << 20).await.map_err(acme_error)?;
+
+        Ok(Issued {
+            key,
+            chain_pem: chain_pem.to_vec(),
+        })
+    }
+
+    async fn authorize(&mut self, authz_url: &str, challenges: &Challenges) -> Result<()> {
+        let mut res = self.post::<()>(authz_url, None).await?;
+        let authz: Authorization = read_json(&mut res).await?;
+        if authz.status == "valid" {
+            return Ok(());
+        }
+
+        let challenge = authz
+            .challenges
+            .into_iter()
+            .find(|c| c.kind == "http-01")
+            .ok_or_else(|| AppError::AcmeError("no http-01 challenge offered".to_string()))?;
+
+        challenges.insert(
+            challenge.token.clone(),
+            self.key.key_authorization(&challenge.token)?,
+        );
+        let result = self.answer(authz_url, &challenge.url).await;
+        challenges.remove(&challenge.token);
+        result
+    }
+
+    async fn answer(&mut self, authz_url: &str, challenge_url: &str) -> Result<()> {
+        self.post(challenge_url, Some(&json!({}))).await?;
+        let authz: Authorization = self
+            .poll(authz_url, |a: &Authorization| a.status != "pending")
+            .await?;
+        if authz.status == "valid" {
+            Ok(())
+        } else {
+            Err(AppError::AcmeError(format!(
+                "authorization {} ended as '{}'",
+                authz_url, authz.status
+            )))
+        }
+    }
+
+    /// POST-as-GET `url` until `done` is true for the returned resource
+    async fn poll<T, F>(&mut self, url: &str, done: F) -> Result<T>
+    where
+        T: DeserializeOwned,
+        F: Fn(&T) -> bool,
+    {
+        for _ in 0..POLL_ATTEMPTS {
+            let mut res = self.post::<()>(url, None).await?;
+            let resource: T = read_json(&mut res).await?;
+            if done(&resource) {
+                return Ok(resource);
+            }
+            delay_for(POLL_INTERVAL).await;
+        }
+        Err(AppError::AcmeError(format!("gave up waiting for {}", url)))
+    }
+
+    async fn new_nonce(&mut self) -> Result<String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let res = self
+            .http
+            .head(&self.directory.new_nonce)
+            .send()
+            .await
+            .map_err(acme_error)?;
+        replay_nonce(&res)
+            .ok_or_else(|| AppError::AcmeError("no Replay-Nonce in newNonce".to_string()))
+    }
+
+    /// Signed POST, retried once when the server rejects the nonce
+    async fn post<T: Serialize>(&mut self, url: &str, payload: Option<&T>) -> Result<Response> {
+        let mut retried = false;
+        loop {
+            let nonce = self.new_nonce().await?;
+            let key_id = if self.account.is_empty() {
+                KeyId::Jwk
+            } else {
+                KeyId::Kid(&self.account)
+            };
+            let body = self.key.sign(url, &nonce, key_id, payload)?;
+
+            let mut res = self
+                .http
+                .post(url)
+                .content_type("application/jose+json")
+                .send_body(body)
+                .await
+                .map_err(acme_error)?;
+            self.nonce = replay_nonce(&res);
+
+            if res.status().is_success() {
+                return Ok(res);
+            }
+
+            let problem = res.body().await.map_err(acme_error)?;
+            let problem = String::from_utf8_lossy(&problem);
+            if !retried && problem.contains("urn:ietf:params:acme:error:badNonce") {
+                retried = true;
+                continue;
+            }
+            return Err(AppError::AcmeError(format!(
+                "{} returned {}: {}",
+                url,
+                res.status(),
+                problem
+            )));
+        }
+    }
+}
+
+fn replay_nonce(res: &Response) -> Option<String> {
+    res.headers()
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+fn location(res: &Response) -> Result<String> {
+    res.headers()
+        .get(header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .ok_or_else(|| AppError::AcmeError("response without a Location header".to_string()))
+}
+
+async fn read_json<T: DeserializeOwned>(res: &mut Response) -> Result<T> {
+    let body = res.body().await.map_err(acme_error)?;
+    serde_json::from_slice(&body).map_err(acme_error)
+}
+
+/// A fresh P-256 key and a DER encoded CSR for `domain`
+fn csr(domain: &str) -> Result<(PKey<Private>, Vec<u8>)> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, domain)?;
+
+    let mut req = X509ReqBuilder::new()?;
+    req.set_subject_name(&name.build())?;
+    req.set_pubkey(&key)?;
+    let san = SubjectAlternativeName::new()
+        .dns(domain)
+        .build(&req.x509v3_context(None))?;
+    let mut extensions = Stack::new()?;
+    extensions.push(san)?;
+    req.add_extensions(&extensions)?;
+    req.sign(&key, MessageDigest::sha256())?;
+
+    Ok((key, req.build().to_der()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::x509::X509Req;
+
+    #[test]
+    fn csr_is_signed_for_the_domain() {
+        let (key, der) = csr("example.com").unwrap();
+        let req = X509Req::from_der(&der).unwrap();
+
+        assert!(req.verify(&key).unwrap());
+        let cn = req
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .unwrap();
+        assert_eq!(cn.data().as_slice(), b"example.com");
+    }
+}
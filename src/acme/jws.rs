@@ -0,0 +1,185 @@
+use super::acme_error;
+use crate::Result;
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    ec::{EcGroup, EcKey},
+    ecdsa::EcdsaSig,
+    hash::{hash, MessageDigest},
+    nid::Nid,
+    pkey::Private,
+};
+use serde::Serialize;
+use serde_json::json;
+
+/// base64url without padding, as used everywhere in ACME
+pub fn b64(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+/// How the signer identifies itself in the protected header
+pub enum KeyId<'a> {
+    /// Full public key, only used to create the account
+    Jwk,
+    /// Account URL returned when the account was created
+    Kid(&'a str),
+}
+
+/// ES256 key used to sign requests to the ACME server
+pub struct AccountKey {
+    key: EcKey<Private>,
+}
+
+impl AccountKey {
+    /// A new P-256 key
+    pub fn generate() -> Result<Self> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        Ok(AccountKey {
+            key: EcKey::generate(&group)?,
+        })
+    }
+
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        Ok(AccountKey {
+            key: EcKey::private_key_from_pem(pem)?,
+        })
+    }
+
+    pub fn to_pem(&self) -> Result<Vec<u8>> {
+        Ok(self.key.private_key_to_pem()?)
+    }
+
+    /// Public key as a JWK with the members in lexicographic order,
+    /// the order matters for the thumbprint
+    pub fn jwk(&self) -> Result<serde_json::Value> {
+        let mut ctx = BigNumContext::new()?;
+        let mut x = BigNum::new()?;
+        let mut y = BigNum::new()?;
+        self.key
+            .public_key()
+            .affine_coordinates_gfp(self.key.group(), &mut x, &mut y, &mut ctx)?;
+
+        Ok(json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": b64(&pad(&x.to_vec(), 32)),
+            "y": b64(&pad(&y.to_vec(), 32)),
+        }))
+    }
+
+    /// RFC 7638 thumbprint, part of every key authorization
+    pub fn thumbprint(&self) -> Result<String> {
+        let jwk = serde_json::to_vec(&self.jwk()?).map_err(acme_error)?;
+        Ok(b64(&hash(MessageDigest::sha256(), &jwk)?))
+    }
+
+    /// `token.thumbprint`, the content served for an HTTP-01 challenge
+    pub fn key_authorization(&self, token: &str) -> Result<String> {
+        Ok(format!("{}.{}", token, self.thumbprint()?))
+    }
+
+    /// Flattened JWS body for a POST to `url`. A `None` payload signs an empty
+    /// string, which is how ACME does POST-as-GET.
+    pub fn sign<T: Serialize>(
+        &self,
+        url: &str,
+        nonce: &str,
+        key_id: KeyId<'_>,
+        payload: Option<&T>,
+    ) -> Result<String> {
+        let mut protected = json!({
+            "alg": "ES256",
+            "nonce": nonce,
+            "url": url,
+        });
+        match key_id {
+            KeyId::Jwk => protected["jwk"] = self.jwk()?,
+            KeyId::Kid(kid) => protected["kid"] = json!(kid),
+        }
+
+        let protected = b64(&serde_json::to_vec(&protected).map_err(acme_error)?);
+        let payload = match payload {
+            Some(p) => b64(&serde_json::to_vec(p).map_err(acme_error)?),
+            None => String::new(),
+        };
+
+        let digest = hash(
+            MessageDigest::sha256(),
+            format!("{}.{}", protected, payload).as_bytes(),
+        )?;
+        let sig = EcdsaSig::sign(&digest, &self.key)?;
+        let mut signature = pad(&sig.r().to_vec(), 32);
+        signature.extend(pad(&sig.s().to_vec(), 32));
+
+        serde_json::to_string(&json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": b64(&signature),
+        }))
+        .map_err(acme_error)
+    }
+}
+
+/// Left pad big endian numbers to a fixed width
+fn pad(bytes: &[u8], len: usize) -> Vec<u8> {
+    let mut padded = vec![0; len.saturating_sub(bytes.len())];
+    padded.extend_from_slice(bytes);
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_verifies_with_the_jwk() {
+        let key = AccountKey::generate().unwrap();
+        let body = key
+            .sign(
+                "https://acme/new-order",
+                "nonce",
+                KeyId::Kid("kid"),
+                Some(&json!({})),
+            )
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+        let signature =
+            base64::decode_config(body["signature"].as_str().unwrap(), base64::URL_SAFE_NO_PAD)
+                .unwrap();
+        assert_eq!(signature.len(), 64);
+
+        let sig = EcdsaSig::from_private_components(
+            BigNum::from_slice(&signature[..32]).unwrap(),
+            BigNum::from_slice(&signature[32..]).unwrap(),
+        )
+        .unwrap();
+        let signed = format!(
+            "{}.{}",
+            body["protected"].as_str().unwrap(),
+            body["payload"].as_str().unwrap()
+        );
+        let digest = hash(MessageDigest::sha256(), signed.as_bytes()).unwrap();
+        assert!(sig.verify(&digest, &key.key).unwrap());
+    }
+
+    #[test]
+    fn post_as_get_has_empty_payload() {
+        let key = AccountKey::generate().unwrap();
+        let body = key
+            .sign::<()>("https://acme/order/1", "nonce", KeyId::Kid("kid"), None)
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["payload"], "");
+    }
+
+    #[test]
+    fn thumbprint_is_stable_across_pem_round_trip() {
+        let key = AccountKey::generate().unwrap();
+        let copy = AccountKey::from_pem(&key.to_pem().unwrap()).unwrap();
+        assert_eq!(key.thumbprint().unwrap(), copy.thumbprint().unwrap());
+        assert_eq!(
+            key.key_authorization("token").unwrap(),
+            format!("token.{}", key.thumbprint().unwrap())
+        );
+    }
+}
@@ -0,0 +1,131 @@
+mod client;
+mod jws;
+mod store;
+
+pub use store::days_until_expiry;
+pub(crate) use store::write_key_pair;
+
+use client::AcmeClient;
+use store::CertStore;
+
+use crate::{error::AppError, tls::ReloadableCert, LetsEncryptConf, Result, SSLConf};
+use actix_rt::time::delay_for;
+use log::{error, info};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+/// How often the renewal task checks the certificate
+const RENEWAL_CHECK: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Pending HTTP-01 challenges, token to key authorization.
+/// Shared between the ACME client and the challenge listener.
+#[derive(Clone, Default, Debug)]
+pub struct Challenges(Arc<RwLock<HashMap<String, String>>>);
+
+impl Challenges {
+    /// Serve `key_authorization` for `token` until it is removed
+    pub fn insert(&self, token: String, key_authorization: String) {
+        self.0
+            .write()
+            .expect("challenge lock poisoned")
+            .insert(token, key_authorization);
+    }
+
+    /// Stop serving `token`
+    pub fn remove(&self, token: &str) {
+        self.0
+            .write()
+            .expect("challenge lock poisoned")
+            .remove(token);
+    }
+
+    /// Key authorization to answer with for `token`
+    pub fn get(&self, token: &str) -> Option<String> {
+        self.0
+            .read()
+            .expect("challenge lock poisoned")
+            .get(token)
+            .cloned()
+    }
+}
+
+/// Issues and renews the certificate for `LetsEncryptConf::domain`
+#[derive(Debug)]
+pub struct Acme {
+    conf: LetsEncryptConf,
+    store: CertStore,
+    challenges: Challenges,
+}
+
+impl Acme {
+    /// Writes the certificate and key to the paths in `ssl`, the key encrypted
+    /// with `ssl.key_passphrase` when there is one
+    pub fn new(conf: &LetsEncryptConf, ssl: &SSLConf) -> Self {
+        Acme {
+            store: CertStore::new(&conf.storage_dir, ssl),
+            conf: conf.clone(),
+            challenges: Challenges::default(),
+        }
+    }
+
+    /// Challenges to serve on the HTTP-01 listener
+    pub fn challenges(&self) -> Challenges {
+        self.challenges.clone()
+    }
+
+    /// True when there is a certificate that hasn't expired yet, so a failed
+    /// `ensure_certificate` can wait for `renew_forever`
+    pub fn has_usable_certificate(&self) -> bool {
+        self.store.usable()
+    }
+
+    /// Issue a certificate if there is none, or the current one expires within
+    /// `renew_before_days`. Returns true when a new certificate was written.
+    pub async fn ensure_certificate(&self) -> Result<bool> {
+        if !self.store.needs_renewal(self.conf.renew_before_days)? {
+            return Ok(false);
+        }
+
+        let mut client = AcmeClient::connect(
+            &self.conf.directory_url,
+            self.conf.ca_file.as_deref(),
+            self.store.account_key()?,
+            &self.conf.email,
+        )
+        .await?;
+        let issued = client.issue(&self.conf.domain, &self.challenges).await?;
+        self.store.save(&issued)?;
+        Ok(true)
+    }
+
+    /// Check the certificate twice a day, for as long as the server runs,
+    /// and have `cert` serve a renewed one
+    pub async fn renew_forever(self, cert: ReloadableCert) {
+        loop {
+            delay_for(RENEWAL_CHECK).await;
+            match self.ensure_certificate().await {
+                Ok(true) => {
+                    info!("renewed certificate for {}", self.conf.domain);
+                    if let Err(e) = cert.reload() {
+                        error!(
+                            "could not load the renewed certificate for {}: {}",
+                            self.conf.domain, e
+                        );
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => error!(
+                    "could not renew certificate for {}: {}",
+                    self.conf.domain, e
+                ),
+            }
+        }
+    }
+}
+
+fn acme_error<E: std::fmt::Display>(e: E) -> AppError {
+    AppError::AcmeError(e.to_string())
+}
@@ -0,0 +1,30 @@
+use super::{AppEnv, Override};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Command line flags, the highest precedence configuration layer
+#[derive(StructOpt, Debug, Default)]
+#[structopt(name = "rust_at_one", about = "Rust at One API server")]
+pub struct Args {
+    /// TOML config file, applied on top of config/default.toml and the profile file
+    #[structopt(short, long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// Named profile (dev, test, prod...), reads config/<profile>.toml and <profile>.env
+    #[structopt(short, long, env = "APP_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Override a single setting, e.g. `--set mongo.db_name=rust_at_one`. Can be repeated
+    #[structopt(short = "s", long = "set", number_of_values = 1)]
+    pub overrides: Vec<Override>,
+}
+
+impl Args {
+    /// The environment selected by the flags
+    pub fn app_env(&self) -> AppEnv<'_> {
+        match &self.profile {
+            Some(profile) => AppEnv::Profile(profile),
+            None => AppEnv::Default,
+        }
+    }
+}
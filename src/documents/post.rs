@@ -1,7 +1,8 @@
-use crate::mongo::Mongo;
+use crate::{error::AppError, mongo::Mongo};
 use mongodb::bson::{doc, Document};
 use mongodb::options::UpdateModifications;
 use serde::{Deserialize, Serialize};
+use std::convert::{TryFrom, TryInto};
 
 /// Data model for Post
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -14,17 +15,23 @@ pub struct Post {
     pub author: Option<String>,
 }
 
-impl From<Post> for UpdateModifications {
-    fn from(p: Post) -> Self {
-        let mut base_doc: Document = p.into();
+impl TryFrom<Post> for UpdateModifications {
+    type Error = AppError;
+
+    fn try_from(p: Post) -> Result<Self, Self::Error> {
+        let mut base_doc: Document = p.try_into()?;
         base_doc.remove("_id");
         let document = doc! {"$set": base_doc};
-        UpdateModifications::Document(document)
+        Ok(UpdateModifications::Document(document))
     }
 }
 
-impl From<Post> for Document {
-    fn from(p: Post) -> Self {
+/// Fails with `AppError::BadRequest` on an id that isn't a valid id,
+/// dropping it would leave a filter matching every post
+impl TryFrom<Post> for Document {
+    type Error = AppError;
+
+    fn try_from(p: Post) -> Result<Self, Self::Error> {
         let mut document = doc! {};
 
         if let Some(name) = p.name {
@@ -36,12 +43,11 @@ impl From<Post> for Document {
         }
 
         if let Some(id) = p.id {
-            if let Ok(obejct_id) = Mongo::to_object_id(id.as_str()) {
-                document.insert("_id", obejct_id);
-            }
+            let obejct_id = Mongo::to_object_id(id.as_str())?;
+            document.insert("_id", obejct_id);
         }
 
-        document
+        Ok(document)
     }
 }
 
@@ -125,4 +131,25 @@ mod tests {
         let r = serde_json::to_string(&i).unwrap();
         assert_eq!(r, e);
     }
+
+    #[test]
+    fn invalid_ids_fail_the_conversion() {
+        let post = |id: Option<&str>| Post {
+            id: id.map(str::to_string),
+            name: Some("One".to_string()),
+            author: None,
+        };
+        match Document::try_from(post(Some("not-an-id"))) {
+            Err(AppError::BadRequest(m)) => assert_eq!(m, "'not-an-id' is not a valid id"),
+            other => panic!("{:?}", other),
+        }
+        assert!(UpdateModifications::try_from(post(Some("not-an-id"))).is_err());
+
+        let document = Document::try_from(post(Some("5f1d7a3e9c1b2a0012345678"))).unwrap();
+        assert_eq!(
+            document.get_object_id("_id").unwrap().to_hex(),
+            "5f1d7a3e9c1b2a0012345678"
+        );
+        assert!(!Document::try_from(post(None)).unwrap().contains_key("_id"));
+    }
 }
@@ -1,10 +1,12 @@
+use crate::mongo::{DbFailure, RETRY_AFTER_SECS};
 use actix_web::{
     dev::HttpResponseBuilder,
-    error::ResponseError,
-    http::{header, StatusCode},
+    error::{JsonPayloadError, PathError, QueryPayloadError, ResponseError},
+    http::{header, HeaderValue, StatusCode},
     HttpResponse,
 };
-use serde::Serialize;
+use log::error;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,8 +20,11 @@ pub enum AppError {
     #[error(transparent)]
     DbError(#[from] mongodb::error::Error),
 
-    #[error(transparent)]
-    SSLError(#[from] openssl::error::ErrorStack),
+    #[error("Mongo: {0}")]
+    DbUnreachable(String),
+
+    #[error("TLS: {0}")]
+    SSLError(String),
 
     #[error("{0}")]
     BadRequest(String),
@@ -27,8 +32,26 @@ pub enum AppError {
     #[error("{0}")]
     NotFound(String),
 
+    #[error("{0} is not allowed on this resource")]
+    MethodNotAllowed(String),
+
     #[error("You are unauthorized to access this data")]
     Forbidden,
+
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+
+    #[error("ACME: {0}")]
+    AcmeError(String),
+
+    #[error("{} field(s) failed validation", .0.len())]
+    ValidationError(Vec<FieldError>),
+}
+
+impl From<openssl::error::ErrorStack> for AppError {
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        AppError::SSLError(e.to_string())
+    }
 }
 
 impl ResponseError for AppError {
@@ -36,27 +59,243 @@ impl ResponseError for AppError {
         match self {
             AppError::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::IOError(_) => StatusCode::SERVICE_UNAVAILABLE,
-            AppError::DbError(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::DbError(e) => match DbFailure::from(e) {
+                DbFailure::Conflict => StatusCode::CONFLICT,
+                DbFailure::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+                DbFailure::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            AppError::DbUnreachable(_) => StatusCode::SERVICE_UNAVAILABLE,
             AppError::SSLError(_) => StatusCode::SERVICE_UNAVAILABLE,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
             AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::ConfigError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::AcmeError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
-        let status_code = self.status_code();
-        HttpResponseBuilder::new(status_code)
-            .set_header(header::CONTENT_TYPE, "application/json; charset=utf-8")
-            .json(ErrorMessage {
-                message: self.to_string(),
-                status: status_code.to_string(),
-            })
+        if let AppError::DbError(e) = self {
+            error!("mongo: {}", e);
+        }
+        let mut res = self.problem().response();
+        if let Some(secs) = self.retry_after() {
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        res
+    }
+}
+
+impl AppError {
+    /// Stable identifier of the variant, part of the API contract
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::InternalServerError(_) => "internal_error",
+            AppError::IOError(_) => "io_error",
+            AppError::DbError(e) => match DbFailure::from(e) {
+                DbFailure::Conflict => "conflict",
+                DbFailure::Unavailable => "database_unavailable",
+                DbFailure::Internal => "database_error",
+            },
+            AppError::DbUnreachable(_) => "database_unavailable",
+            AppError::SSLError(_) => "tls_error",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::NotFound(_) => "not_found",
+            AppError::MethodNotAllowed(_) => "method_not_allowed",
+            AppError::Forbidden => "forbidden",
+            AppError::ConfigError(_) => "config_error",
+            AppError::AcmeError(_) => "acme_error",
+            AppError::ValidationError(_) => "validation_failed",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            AppError::InternalServerError(_)
+            | AppError::ConfigError(_)
+            | AppError::AcmeError(_) => "Internal server error",
+            AppError::DbError(e) => match DbFailure::from(e) {
+                DbFailure::Conflict => "Conflict",
+                DbFailure::Unavailable => "Service unavailable",
+                DbFailure::Internal => "Internal server error",
+            },
+            AppError::IOError(_) | AppError::DbUnreachable(_) | AppError::SSLError(_) => {
+                "Service unavailable"
+            }
+            AppError::BadRequest(_) => "Bad request",
+            AppError::NotFound(_) => "Resource not found",
+            AppError::MethodNotAllowed(_) => "Method not allowed",
+            AppError::Forbidden => "Forbidden",
+            AppError::ValidationError(_) => "Invalid request fields",
+        }
+    }
+
+    /// The `AppError` for a request actix's own extractors rejected
+    pub fn from_extractor(err: &actix_web::Error) -> Option<Self> {
+        let detail = if let Some(e) = err.as_error::<JsonPayloadError>() {
+            match e {
+                JsonPayloadError::Overflow => "JSON body is too large".to_string(),
+                JsonPayloadError::ContentType => {
+                    "expected a body of type application/json".to_string()
+                }
+                JsonPayloadError::Deserialize(e) => format!("invalid JSON body: {}", e),
+                JsonPayloadError::Payload(e) => format!("couldn't read the body: {}", e),
+            }
+        } else if let Some(QueryPayloadError::Deserialize(e)) = err.as_error() {
+            format!("invalid query string: {}", e)
+        } else if let Some(PathError::Deserialize(e)) = err.as_error() {
+            format!("invalid path: {}", e)
+        } else {
+            return None;
+        };
+        Some(AppError::BadRequest(detail))
+    }
+
+    /// What clients are told; driver errors can leak hosts and users, so
+    /// those only reach the logs
+    fn detail(&self) -> String {
+        match self {
+            AppError::DbError(e) => match DbFailure::from(e) {
+                DbFailure::Conflict => "A document with the same unique key already exists",
+                DbFailure::Unavailable => "The database is unavailable, try again later",
+                DbFailure::Internal => "An unexpected database error occurred",
+            }
+            .to_string(),
+            AppError::DbUnreachable(_) => {
+                "The database is unavailable, try again later".to_string()
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /// Seconds to send in `Retry-After`, for errors that are worth retrying
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            AppError::DbError(e) if DbFailure::from(e) == DbFailure::Unavailable => {
+                Some(RETRY_AFTER_SECS)
+            }
+            AppError::DbUnreachable(_) => Some(RETRY_AFTER_SECS),
+            _ => None,
+        }
+    }
+
+    /// The RFC 7807 body for this error, without an `instance`
+    pub fn problem(&self) -> Problem {
+        let errors = match self {
+            AppError::ValidationError(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+        Problem {
+            kind: format!("{}{}", PROBLEM_TYPE_PREFIX, self.code()),
+            title: self.title().to_string(),
+            status: self.status_code().as_u16(),
+            detail: self.detail(),
+            instance: None,
+            code: self.code().to_string(),
+            request_id: None,
+            errors,
+        }
+    }
+}
+
+/// `type` of every problem, followed by the error code
+pub const PROBLEM_TYPE_PREFIX: &str = "urn:rust_at_one:error:";
+
+/// An RFC 7807 problem details body, sent as `application/problem+json`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// URI identifying the kind of problem
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Short summary, the same for every occurrence of the kind
+    pub title: String,
+    /// HTTP status code
+    pub status: u16,
+    /// Explanation of this occurrence
+    pub detail: String,
+    /// The request path the problem occurred on
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub instance: Option<String>,
+    /// Stable error code, see `AppError::code`
+    pub code: String,
+    /// The `X-Request-Id` of the request, to find it in the logs
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub request_id: Option<String>,
+    /// Field level problems for `validation_failed`
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub errors: Vec<FieldError>,
+}
+
+impl Problem {
+    /// The problem as a response with the matching status
+    pub fn response(&self) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        HttpResponseBuilder::new(status)
+            .set_header(header::CONTENT_TYPE, "application/problem+json")
+            .body(serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// A single invalid field in a request, reported through `AppError::ValidationError`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    /// Name of the field, dotted for nested fields
+    pub field: String,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Every missing or malformed setting found while building an `AppConfig`,
+/// collected so they can be reported in one go.
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    problems: Vec<ConfigProblem>,
+}
+
+impl ConfigError {
+    pub fn new(problems: Vec<ConfigProblem>) -> Self {
+        ConfigError { problems }
+    }
+
+    /// The individual problems, in the order they were found
+    pub fn problems(&self) -> &[ConfigProblem] {
+        &self.problems
     }
 }
 
-#[derive(Serialize)]
-struct ErrorMessage {
-    message: String,
-    status: String,
+impl std::error::Error for ConfigError {}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single configuration problem
+#[derive(Error, Debug, PartialEq)]
+pub enum ConfigProblem {
+    #[error("{0} is not set")]
+    Missing(String),
+
+    #[error("{key} is not set, it is required when {by} is true")]
+    RequiredBy { key: String, by: String },
+
+    #[error("{key} is malformed: {reason}")]
+    Malformed { key: String, reason: String },
 }
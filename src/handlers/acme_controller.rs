@@ -0,0 +1,14 @@
+use crate::acme::Challenges;
+use actix_web::{http::header, web, HttpResponse};
+
+pub async fn challenge(
+    token: web::Path<String>,
+    challenges: web::Data<Challenges>,
+) -> HttpResponse {
+    match challenges.get(&token) {
+        Some(key_authorization) => HttpResponse::Ok()
+            .set_header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(key_authorization),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
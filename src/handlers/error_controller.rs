@@ -0,0 +1,104 @@
+use crate::error::AppError;
+
+use super::super::Result;
+use actix_web::{HttpRequest, HttpResponse};
+
+/// Default service, answers every request no route matched
+pub async fn not_found(req: HttpRequest) -> Result<HttpResponse> {
+    Err(AppError::NotFound(format!(
+        "no route for {} {}",
+        req.method(),
+        req.path()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AppState;
+    use crate::{
+        error::Problem, handlers::configure_routes, middleware::ProblemDetails, mongo::Mongo,
+        MongoConf,
+    };
+    use actix_web::{http::StatusCode, test, web, App};
+
+    async fn problem(req: test::TestRequest) -> (StatusCode, Problem) {
+        // the driver connects lazily, nothing is sent to this address
+        let mongo = Mongo::initialize(
+            "mongodb://localhost:1/",
+            "test",
+            &MongoConf {
+                startup_timeout: 0,
+                ..MongoConf::default()
+            },
+        )
+        .await
+        .unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .wrap(ProblemDetails)
+                .app_data(AppState::new(&mongo).wrap())
+                .service(web::scope("/api").configure(configure_routes)),
+        )
+        .await;
+        let res = test::call_service(&mut app, req.to_request()).await;
+        let status = res.status();
+        (
+            status,
+            serde_json::from_slice(&test::read_body(res).await).unwrap(),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn malformed_json() {
+        let (status, problem) = problem(
+            test::TestRequest::post()
+                .uri("/api/posts")
+                .header("content-type", "application/json")
+                .set_payload("{\"title\": "),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(problem.code, "bad_request");
+        assert!(
+            problem.detail.starts_with("invalid JSON body: "),
+            "{}",
+            problem.detail
+        );
+        assert_eq!(problem.instance.as_deref(), Some("/api/posts"));
+    }
+
+    #[actix_rt::test]
+    async fn bad_query_value() {
+        let (status, problem) =
+            problem(test::TestRequest::get().uri("/api/posts?number=abc")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(
+            problem.detail.starts_with("invalid query string: "),
+            "{}",
+            problem.detail
+        );
+    }
+
+    #[actix_rt::test]
+    async fn unknown_route() {
+        for uri in &["/api/nothing", "/api/posts/1/comments"] {
+            let (status, problem) = problem(test::TestRequest::get().uri(uri)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(problem.code, "not_found");
+            assert_eq!(problem.detail, format!("no route for GET {}", uri));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn method_not_allowed() {
+        let requests = vec![
+            test::TestRequest::with_uri("/api/posts").method("PATCH".parse().unwrap()),
+            test::TestRequest::post().uri("/api/posts/5f2d5a4c9d5b3b1e8c6f0a11"),
+        ];
+        for req in requests {
+            let (status, problem) = problem(req).await;
+            assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(problem.code, "method_not_allowed");
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::health::{Health, Status};
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+
+/// The process is running and answering, dependencies aren't checked
+pub async fn live() -> HttpResponse {
+    HttpResponse::Ok().json(json!({"status": Status::Up}))
+}
+
+/// Dependencies are reachable, 503 when any of them is down
+pub async fn ready(health: web::Data<Health>) -> HttpResponse {
+    let report = health.check().await;
+    match report.status {
+        Status::Up => HttpResponse::Ok().json(report),
+        Status::Down => HttpResponse::ServiceUnavailable().json(report),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{handlers::configure_health, health::Health, mongo::Mongo, MongoConf};
+    use actix_web::{http::StatusCode, test, web, App};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    async fn health() -> Health {
+        // Nothing listens on port 1, the ping can't succeed
+        let mongo = Mongo::initialize(
+            "mongodb://localhost:1/",
+            "test",
+            &MongoConf {
+                startup_timeout: 0,
+                ..MongoConf::default()
+            },
+        )
+        .await
+        .unwrap();
+        Health::new(&mongo, Duration::from_millis(200))
+    }
+
+    async fn get(health: Health, uri: &str) -> (StatusCode, Value) {
+        let health = web::Data::new(health);
+        let mut app =
+            test::init_service(App::new().app_data(health).configure(configure_health)).await;
+        let res =
+            test::call_service(&mut app, test::TestRequest::get().uri(uri).to_request()).await;
+        let status = res.status();
+        (
+            status,
+            serde_json::from_slice(&test::read_body(res).await).unwrap(),
+        )
+    }
+
+    #[actix_rt::test]
+    async fn live_without_dependencies() {
+        assert_eq!(
+            get(health().await, "/health/live").await,
+            (StatusCode::OK, json!({"status": "up"}))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn not_ready_without_mongo() {
+        let (status, body) = get(health().await, "/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "down");
+        assert_eq!(body["checks"]["mongo"]["status"], "down");
+        assert_eq!(body["checks"]["mongo"]["error"], "no answer within 200ms");
+        assert!(body["checks"]["mongo"]["latency_ms"].as_f64().unwrap() >= 200.0);
+    }
+
+    #[actix_rt::test]
+    async fn not_ready_while_shutting_down() {
+        let health = health().await;
+        health.clone().shut_down();
+        assert_eq!(
+            get(health.clone(), "/health/ready").await,
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                json!({"status": "down", "shutting_down": true, "checks": {}})
+            )
+        );
+        assert_eq!(
+            get(health, "/health/live").await,
+            (StatusCode::OK, json!({"status": "up"}))
+        );
+    }
+}
@@ -0,0 +1,11 @@
+use crate::metrics;
+use actix_web::{http::header, HttpResponse};
+
+pub async fn metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .set_header(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )
+        .body(metrics::global().render())
+}
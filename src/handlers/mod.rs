@@ -1,14 +1,67 @@
 use actix_web::web;
 
+mod acme_controller;
+mod error_controller;
+mod health_controller;
+mod metrics_controller;
 mod post_controller;
+mod redirect_controller;
 
+pub use error_controller::not_found;
+pub use redirect_controller::HttpsRedirect;
+
+/// API routes. Unknown paths are answered with the `AppError` body;
+/// `ServiceConfig` can't set a default service, so the last resource catches
+/// everything the others didn't. Extractor failures are turned into
+/// `AppError`s by the `ProblemDetails` middleware, scope level extractor
+/// configs would hide the application data from the handlers.
 pub fn configure_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/posts")
-            .route("/{id}", web::get().to(post_controller::get_one))
-            .route("/{id}", web::put().to(post_controller::put))
-            .route("/{id}", web::delete().to(post_controller::delete))
-            .route("", web::get().to(post_controller::get_many))
-            .route("", web::post().to(post_controller::post)),
+            // One resource per path, so other methods get a 405 instead of a 404
+            .service(
+                web::resource("/{id}")
+                    .route(web::get().to(post_controller::get_one))
+                    .route(web::put().to(post_controller::put))
+                    .route(web::delete().to(post_controller::delete)),
+            )
+            .service(
+                web::resource("")
+                    .route(web::get().to(post_controller::get_many))
+                    .route(web::post().to(post_controller::post)),
+            )
+            .default_service(web::route().to(not_found)),
+    )
+    .service(web::resource("/{tail:.*}").to(not_found));
+}
+
+/// Liveness and readiness probes, expects `health::Health` as app data
+pub fn configure_health(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/health")
+            .route("/live", web::get().to(health_controller::live))
+            .route("/ready", web::get().to(health_controller::ready)),
     );
 }
+
+/// Prometheus metrics at `/metrics`, on the API listener or a separate one
+pub fn configure_metrics(cfg: &mut web::ServiceConfig) {
+    cfg.route("/metrics", web::get().to(metrics_controller::metrics));
+}
+
+/// Routes for the plain HTTP listener answering ACME HTTP-01 challenges,
+/// expects `acme::Challenges` as app data
+pub fn configure_acme_challenge(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "/.well-known/acme-challenge/{token}",
+        web::get().to(acme_controller::challenge),
+    );
+}
+
+/// Routes for the plain HTTP listener: ACME HTTP-01 challenges are answered,
+/// everything else is redirected to HTTPS. Expects `acme::Challenges` and
+/// `HttpsRedirect` as app data.
+pub fn configure_https_redirect(cfg: &mut web::ServiceConfig) {
+    configure_acme_challenge(cfg);
+    cfg.service(web::resource("/{tail:.*}").to(redirect_controller::redirect));
+}
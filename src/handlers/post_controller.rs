@@ -10,7 +10,7 @@ use actix_web::{web, HttpResponse, Responder};
 pub async fn get_one(id: web::Path<String>, app: web::Data<AppState>) -> Result<impl Responder> {
     let result = app
         .post_service
-        .get_one(Post::from_string_id(id.into_inner()))
+        .get_one(Post::from_string_id(id.into_inner())?)
         .await?;
 
     Ok(HttpResponse::Ok().json(result))
@@ -35,7 +35,7 @@ pub async fn put(
     let result = app
         .post_service
         .put(
-            Post::from_string_id(id.into_inner()),
+            Post::from_string_id(id.into_inner())?,
             data.into_inner().into(),
         )
         .await?;
@@ -51,7 +51,7 @@ pub async fn post(data: web::Json<PostUpsert>, app: web::Data<AppState>) -> Resu
 pub async fn delete(id: web::Path<String>, app: web::Data<AppState>) -> Result<impl Responder> {
     match app
         .post_service
-        .delete(Post::from_string_id(id.into_inner()))
+        .delete(Post::from_string_id(id.into_inner())?)
         .await
     {
         Ok(_) => Ok(HttpResponse::Ok()),
@@ -0,0 +1,131 @@
+use actix_web::{http::header, http::Method, web, HttpRequest, HttpResponse};
+
+/// Where the plain HTTP listener sends clients
+#[derive(Debug, Clone)]
+pub struct HttpsRedirect {
+    origin: Option<String>,
+    port: u16,
+}
+
+impl HttpsRedirect {
+    /// Redirect to `origin` when set, otherwise to the requested host on `port`
+    pub fn new(origin: Option<String>, port: u16) -> Self {
+        HttpsRedirect { origin, port }
+    }
+
+    fn origin_for(&self, host: &str) -> String {
+        if let Some(origin) = &self.origin {
+            return origin.clone();
+        }
+        let host = strip_port(host);
+        if self.port == 443 {
+            format!("https://{}", host)
+        } else {
+            format!("https://{}:{}", host, self.port)
+        }
+    }
+}
+
+/// `example.com:80` to `example.com`, `[::1]:80` to `[::1]`
+fn strip_port(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    }
+}
+
+/// 301 for GET and HEAD, 308 for everything else so clients keep the method and body
+pub async fn redirect(req: HttpRequest, target: web::Data<HttpsRedirect>) -> HttpResponse {
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let location = format!(
+        "{}{}",
+        target.origin_for(req.connection_info().host()),
+        path
+    );
+
+    let mut response = match *req.method() {
+        Method::GET | Method::HEAD => HttpResponse::MovedPermanently(),
+        _ => HttpResponse::PermanentRedirect(),
+    };
+    response.set_header(header::LOCATION, location).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{acme::Challenges, handlers::configure_https_redirect};
+    use actix_web::{http::StatusCode, test, App};
+
+    async fn location(target: HttpsRedirect, req: test::TestRequest) -> (StatusCode, String) {
+        let challenges = Challenges::default();
+        challenges.insert("token".to_string(), "token.thumbprint".to_string());
+        let mut app = test::init_service(
+            App::new()
+                .data(target)
+                .data(challenges)
+                .configure(configure_https_redirect),
+        )
+        .await;
+
+        let res = test::call_service(&mut app, req.to_request()).await;
+        let location = res
+            .headers()
+            .get(header::LOCATION)
+            .map(|l| l.to_str().unwrap().to_string())
+            .unwrap_or_default();
+        (res.status(), location)
+    }
+
+    #[actix_rt::test]
+    async fn keeps_path_and_query() {
+        let req = test::TestRequest::get()
+            .uri("/api/posts?name=One&count=2")
+            .header(header::HOST, "example.com:80");
+        assert_eq!(
+            location(HttpsRedirect::new(None, 443), req).await,
+            (
+                StatusCode::MOVED_PERMANENTLY,
+                "https://example.com/api/posts?name=One&count=2".to_string()
+            )
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/posts")
+            .header(header::HOST, "[::1]:8080");
+        assert_eq!(
+            location(HttpsRedirect::new(None, 8443), req).await,
+            (
+                StatusCode::PERMANENT_REDIRECT,
+                "https://[::1]:8443/api/posts".to_string()
+            )
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .header(header::HOST, "internal");
+        assert_eq!(
+            location(
+                HttpsRedirect::new(Some("https://example.com".to_string()), 8443),
+                req
+            )
+            .await,
+            (
+                StatusCode::MOVED_PERMANENTLY,
+                "https://example.com/".to_string()
+            )
+        );
+    }
+
+    #[actix_rt::test]
+    async fn acme_challenges_are_not_redirected() {
+        let req = test::TestRequest::get().uri("/.well-known/acme-challenge/token");
+        assert_eq!(
+            location(HttpsRedirect::new(None, 443), req).await,
+            (StatusCode::OK, String::new())
+        );
+    }
+}
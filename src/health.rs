@@ -0,0 +1,106 @@
+use crate::mongo::Mongo;
+use actix_rt::time::timeout;
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Whether the service or one of its dependencies can serve requests
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Up,
+    Down,
+}
+
+/// Outcome of checking one dependency
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Check {
+    pub status: Status,
+    /// How long the check took
+    pub latency_ms: f64,
+    /// Why the dependency is down, without driver details
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The readiness report, down when any dependency is down or the service
+/// is shutting down
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Report {
+    pub status: Status,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub shutting_down: bool,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+/// Checks the dependencies the readiness probe reports on
+#[derive(Clone)]
+pub struct Health {
+    mongo: Mongo,
+    timeout: Duration,
+    shutting_down: Arc<AtomicBool>,
+}
+
+impl Health {
+    /// Every check gives up after `timeout`
+    pub fn new(mongo: &Mongo, timeout: Duration) -> Self {
+        Health {
+            mongo: mongo.clone(),
+            timeout,
+            shutting_down: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// From now on every clone reports down, without checking dependencies
+    pub fn shut_down(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub async fn check(&self) -> Report {
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return Report {
+                status: Status::Down,
+                shutting_down: true,
+                checks: BTreeMap::new(),
+            };
+        }
+
+        let mut checks = BTreeMap::new();
+        checks.insert("mongo", self.mongo().await);
+
+        let up = checks.values().all(|c| c.status == Status::Up);
+        Report {
+            status: if up { Status::Up } else { Status::Down },
+            shutting_down: false,
+            checks,
+        }
+    }
+
+    async fn mongo(&self) -> Check {
+        let started = Instant::now();
+        let error = match timeout(self.timeout, self.mongo.ping()).await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => {
+                warn!("readiness: mongo ping failed: {}", e);
+                Some("ping failed".to_string())
+            }
+            Err(_) => {
+                warn!("readiness: mongo ping timed out after {:?}", self.timeout);
+                Some(format!("no answer within {}ms", self.timeout.as_millis()))
+            }
+        };
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+        Check {
+            status: if error.is_none() {
+                Status::Up
+            } else {
+                Status::Down
+            },
+            latency_ms: (latency_ms * 1000.0).round() / 1000.0,
+            error,
+        }
+    }
+}
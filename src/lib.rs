@@ -1,144 +1,52 @@
 #[warn(missing_debug_implementations, rust_2018_idioms, missing_docs)]
-
+/// Let's encrypt certificates through ACME
+pub mod acme;
+/// Layered configuration
+pub mod config;
 /// Module for documents/models
 pub mod documents;
 /// Error types
 pub mod error;
 /// Endpoint handlers
 pub mod handlers;
+/// Liveness and readiness checks
+pub mod health;
+/// Pretty or JSON lines logging
+pub mod logging;
+/// Prometheus metrics
+pub mod metrics;
+/// Request middleware
+pub mod middleware;
 /// Mongo specific logic
 pub mod mongo;
 /// Abstraction layer, data manipulation logic
 pub mod services;
+/// Graceful shutdown on SIGTERM
+pub mod shutdown;
+#[cfg(test)]
+mod test_dir;
+/// TLS acceptors and certificate reloading
+pub mod tls;
+
+pub use config::{
+    Alpn, AppConfig, AppEnv, Args, CertBinding, ClientVerify, HealthConf, LetsEncryptConf, LogConf,
+    LogFormat, MetricsConf, MongoConf, MongoTlsConf, Passphrase, SSLConf, Secret, TlsProfile,
+    TlsVersion,
+};
+pub use tls::ssl_builder;
 
 use actix_web::web;
-use dotenv::dotenv;
 use error::AppError;
 use mongo::Mongo;
-use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
 use services::{post_service::PostService, DocumentService};
 
 pub type Result<T, E = AppError> = core::result::Result<T, E>;
 
-/// Configuration for the application
-#[derive(Debug, PartialEq)]
-pub struct AppConfig {
-    /// Mongo db connection string
-    pub mongo_db_uri: String,
-    /// Database to use
-    pub db_name: String,
-    /// SSL configs
-    pub ssl_conf: Option<SSLConf>,
-}
-
-#[derive(Debug, PartialEq)]
-pub struct SSLConf {
-    pub lets_encrypt: Option<LetsEncryptConf>,
-    pub key_pem: String,
-    pub cert_pem: String,
-}
-
-#[derive(Debug, PartialEq)]
-pub struct LetsEncryptConf {
-    pub email: String,
-    pub domain: String,
-}
-
-pub enum AppEnv<'a> {
-    Default,
-    TryFromFile(&'a str),
-}
-
-impl AppConfig {
-    pub fn new(env: AppEnv) -> Self {
-        match env {
-            AppEnv::Default => {
-                dotenv().ok();
-            }
-            AppEnv::TryFromFile(s) => {
-                if std::fs::File::open(s).is_ok() {
-                    dotenv::from_filename(s).ok();
-                } else {
-                    dotenv().ok();
-                }
-            }
-        };
-
-        Self::create(
-            dotenv::var("MONGODB_URI"),
-            dotenv::var("DB_NAME"),
-            dotenv::var("USE_SSL"),
-            dotenv::var("USE_LE"),
-            dotenv::var("LE_EMAIL"),
-            dotenv::var("LE_DOMAIN"),
-            dotenv::var("KEY_PEM"),
-            dotenv::var("CERT_PEM"),
-        )
-    }
-
-    fn create(
-        mongo_db: Result<String, dotenv::Error>,
-        db_name: Result<String, dotenv::Error>,
-        use_ssl: Result<String, dotenv::Error>,
-        use_le: Result<String, dotenv::Error>,
-        le_email: Result<String, dotenv::Error>,
-        le_doman: Result<String, dotenv::Error>,
-        key_pem: Result<String, dotenv::Error>,
-        cert_pem: Result<String, dotenv::Error>,
-    ) -> AppConfig {
-        let mut config = AppConfig {
-            mongo_db_uri: mongo_db.expect("MONGODB_URI was not found in environmental variables"),
-            db_name: db_name.expect("DB_NAME was not found in environmental variables"),
-            ssl_conf: None,
-        };
-
-        if use_ssl
-            .map(|v| v.to_lowercase() == "true" || v.to_lowercase() == "1")
-            .unwrap_or(false)
-        {
-            let mut ssl_conf = SSLConf {
-                lets_encrypt: None,
-                key_pem: key_pem
-                    .expect("USE_SSL is true, KEY_PEM was not found in environmental variables"),
-                cert_pem: cert_pem
-                    .expect("USE_SSL is true, KEY_PEM was not found in environmental variables"),
-            };
-
-            if use_le
-                .map(|v| v.to_lowercase() == "true" || v.to_lowercase() == "1")
-                .unwrap_or(false)
-            {
-                ssl_conf.lets_encrypt = Some(LetsEncryptConf {
-                    email: le_email.expect(
-                        "USE_LE is true, LE_EMAIL was not found in environmental variables",
-                    ),
-                    domain: le_doman.expect(
-                        "USE_LE is true, LE_DOMAIN was not found in environmental variables",
-                    ),
-                });
-            }
-            config.ssl_conf = Some(ssl_conf);
-        }
-        config
-    }
-}
-
-// load ssl keys
-// to create a self-signed temporary cert for testing:
-// `openssl req -x509 -newkey rsa:4096 -nodes -keyout key.pem -out cert.pem -days 365 -subj '/CN=localhost'`
-// `openssl rsa -in key.pem -out nopass.pem`
-pub fn ssl_builder(cert: &str, key: &str) -> Result<SslAcceptorBuilder> {
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
-    builder.set_private_key_file(key, SslFiletype::PEM)?;
-    builder.set_certificate_chain_file(cert)?;
-    Ok(builder)
-}
-
 pub struct AppState {
     pub post_service: PostService,
 }
 
-impl<'a> AppState {
+impl AppState {
     pub fn new(mongo: &Mongo) -> Self {
         let post_service = PostService::new(mongo);
         AppState { post_service }
@@ -149,74 +57,3 @@ impl<'a> AppState {
         web::Data::new(self)
     }
 }
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use dotenv::{self, Error};
-    use std::{fs::File, io::Write};
-
-    #[test]
-    fn app_conf_ssl_no_le() {
-        let exp = AppConfig {
-            mongo_db_uri: "a".to_string(),
-            db_name: "b".to_string(),
-            ssl_conf: Some(SSLConf {
-                lets_encrypt: None,
-                key_pem: "k".to_string(),
-                cert_pem: "c".to_string(),
-            }),
-        };
-
-        let conf = AppConfig::create(
-            Ok("a".to_string()),
-            Ok("b".to_string()),
-            Ok("1".to_string()),
-            Err(Error::LineParse("false".to_string(), 1)),
-            Ok("".to_string()),
-            Err(Error::LineParse("".to_string(), 1)),
-            Ok("k".to_string()),
-            Ok("c".to_string()),
-        );
-
-        assert_eq!(conf, exp);
-    }
-
-    #[test]
-    fn app_conf_no_ssl() {
-        let exp = AppConfig {
-            mongo_db_uri: "a".to_string(),
-            db_name: "b".to_string(),
-            ssl_conf: None,
-        };
-
-        let conf = AppConfig::create(
-            Ok("a".to_string()),
-            Ok("b".to_string()),
-            Err(Error::LineParse("0".to_string(), 1)),
-            Ok("true".to_string()),
-            Err(Error::LineParse("".to_string(), 1)),
-            Ok("".to_string()),
-            Err(Error::LineParse("".to_string(), 1)),
-            Ok("c".to_string()),
-        );
-
-        assert_eq!(conf, exp);
-    }
-
-    #[test]
-    #[should_panic(
-        expected = "USE_SSL is true, KEY_PEM was not found in environmental variables: LineParse(\"\", 1)"
-    )]
-    fn app_conf_ssl_missing_conf() {
-        AppConfig::create(
-            Ok("a".to_string()),
-            Ok("b".to_string()),
-            Ok("1".to_string()),
-            Err(Error::LineParse("".to_string(), 1)),
-            Err(Error::LineParse("".to_string(), 1)),
-            Ok("".to_string()),
-            Err(Error::LineParse("".to_string(), 1)),
-            Ok("c".to_string()),
-        );
-    }
-}
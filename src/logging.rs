@@ -0,0 +1,161 @@
+use crate::config::{LogConf, LogFormat};
+use crate::middleware::RequestId;
+use chrono::{SecondsFormat, Utc};
+use log::{Level, Log, Metadata, Record};
+use serde_json::{Map, Value};
+use std::fmt::{self, Write as _};
+use std::io::Write as _;
+use std::sync::OnceLock;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Install the global logger, later calls keep the first configuration
+pub fn init(conf: LogConf) {
+    let logger = LOGGER.get_or_init(|| Logger { conf });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(logger.conf.level);
+    }
+}
+
+/// Log a record with structured fields, they become members of the JSON
+/// object or `key=value` pairs after the message
+pub fn write(level: Level, target: &str, message: fmt::Arguments<'_>, fields: &[(&str, Value)]) {
+    if let Some(logger) = LOGGER.get() {
+        if level <= logger.conf.level {
+            logger.write(level, target, message, fields);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Logger {
+    conf: LogConf,
+}
+
+impl Logger {
+    fn write(
+        &self,
+        level: Level,
+        target: &str,
+        message: fmt::Arguments<'_>,
+        fields: &[(&str, Value)],
+    ) {
+        let request_id = RequestId::current();
+        let line = format_line(
+            self.conf.format,
+            &Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            level,
+            target,
+            message,
+            fields,
+            request_id.as_ref().map(RequestId::as_str),
+        );
+        let _ = writeln!(std::io::stderr().lock(), "{}", line);
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.conf.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            self.write(record.level(), record.target(), *record.args(), &[]);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// A record as a single line, the request id is added to records logged
+/// while serving a request
+fn format_line(
+    format: LogFormat,
+    timestamp: &str,
+    level: Level,
+    target: &str,
+    message: fmt::Arguments<'_>,
+    fields: &[(&str, Value)],
+    request_id: Option<&str>,
+) -> String {
+    let request_id = request_id.map(|id| ("request_id", Value::from(id)));
+    let fields = fields.iter().cloned().chain(request_id);
+    match format {
+        LogFormat::Pretty => {
+            let mut line = format!("{} {:<5} {} {}", timestamp, level, target, message);
+            for (key, value) in fields {
+                let _ = match value {
+                    Value::String(s) => write!(line, " {}={}", key, s),
+                    v => write!(line, " {}={}", key, v),
+                };
+            }
+            line
+        }
+        LogFormat::Json => {
+            let mut object = Map::new();
+            object.insert("timestamp".to_string(), timestamp.into());
+            object.insert("level".to_string(), level.to_string().into());
+            object.insert("target".to_string(), target.into());
+            object.insert("message".to_string(), message.to_string().into());
+            for (key, value) in fields {
+                object.insert(key.to_string(), value);
+            }
+            Value::Object(object).to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: &str = "2020-08-01T12:00:00.000Z";
+
+    fn fields() -> Vec<(&'static str, Value)> {
+        vec![("path", "/api/posts".into()), ("status", 200.into())]
+    }
+
+    #[test]
+    fn pretty_lines() {
+        let line = format_line(
+            LogFormat::Pretty,
+            NOW,
+            Level::Info,
+            "access",
+            format_args!("request completed"),
+            &fields(),
+            None,
+        );
+        assert_eq!(
+            line,
+            "2020-08-01T12:00:00.000Z INFO  access request completed path=/api/posts status=200"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let line = format_line(
+            LogFormat::Json,
+            NOW,
+            Level::Warn,
+            "rust_at_one::tls",
+            format_args!("reload failed: {}", "gone"),
+            &fields(),
+            Some("abc"),
+        );
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "timestamp": NOW,
+                "level": "WARN",
+                "target": "rust_at_one::tls",
+                "message": "reload failed: gone",
+                "path": "/api/posts",
+                "status": 200,
+                "request_id": "abc",
+            })
+        );
+    }
+}
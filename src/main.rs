@@ -1,35 +1,187 @@
-use actix_web::{web, App, HttpServer};
+use actix_web::{middleware::Condition, web, App, HttpServer};
+use log::{error, info, warn};
 use rust_at_one::{
-    handlers::configure_routes, mongo::Mongo, ssl_builder, AppConfig, AppEnv, AppState, Result,
+    acme::Acme,
+    config::ServerConf,
+    handlers::{
+        configure_acme_challenge, configure_health, configure_https_redirect, configure_metrics,
+        configure_routes, not_found, HttpsRedirect,
+    },
+    health::Health,
+    logging, metrics,
+    middleware::{AccessLog, ProblemDetails, RequestIds, RequestMetrics},
+    mongo::Mongo,
+    shutdown,
+    tls::{https_server, ReloadableCert},
+    AppConfig, AppState, Args, LogConf, Result,
 };
+use std::time::Duration;
+use structopt::StructOpt;
 
 #[actix_rt::main]
-async fn main() -> Result<()> {
-    let config = AppConfig::new(AppEnv::Default);
+async fn main() {
+    if let Err(e) = start().await {
+        // Configuration errors happen before the configured logger is installed.
+        // A logger that is installed may be off, so the error also goes to
+        // stderr as is and a failed start is never silent.
+        logging::init(LogConf::default());
+        error!("rust_at_one failed to start: {}", e);
+        eprintln!("rust_at_one failed to start: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn start() -> Result<()> {
+    let args = Args::from_args();
+    let config = AppConfig::from_args(&args)?;
+    logging::init(config.log);
+    // Secrets print as <redacted>
+    info!("configuration: {:?}", config);
     run_application(config).await
 }
 
 async fn run_application(config: AppConfig) -> Result<()> {
-    let mongo = Mongo::initialize(config.mongo_db_uri.as_str(), config.db_name.as_str()).await?;
+    let mongo = Mongo::initialize(
+        config.mongo_db_uri.expose(),
+        config.db_name.as_str(),
+        &config.mongo,
+    )
+    .await?;
     let app_state = AppState::new(&mongo).wrap();
+    let health = web::Data::new(Health::new(
+        &mongo,
+        Duration::from_millis(config.health.ready_timeout),
+    ));
 
-    let mut server = HttpServer::new(move || {
-        let m = App::new()
+    let readiness = health.clone();
+    let server_conf = config.server;
+    let metrics_conf = config.metrics;
+    let metrics_inline = metrics_conf.enabled && metrics_conf.listen.is_none();
+    let app = move || {
+        App::new()
+            .wrap(ProblemDetails)
+            .wrap(Condition::new(metrics_conf.enabled, RequestMetrics))
+            .wrap(AccessLog)
+            .wrap(RequestIds)
             .app_data(app_state.clone())
-            .service(web::scope("/api").configure(configure_routes));
-        m
-    });
+            .app_data(health.clone())
+            .configure(configure_health)
+            .configure(|cfg| {
+                if metrics_inline {
+                    configure_metrics(cfg)
+                }
+            })
+            .service(web::scope("/api").configure(configure_routes))
+            .default_service(web::route().to(not_found))
+    };
+
+    // Listeners besides the API ones, stopped along with them
+    let mut others = Vec::new();
+    if let (true, Some(addr)) = (metrics_conf.enabled, metrics_conf.listen) {
+        let server = HttpServer::new(|| App::new().configure(configure_metrics))
+            .workers(1)
+            .disable_signals()
+            .shutdown_timeout(server_conf.shutdown_timeout)
+            .bind(addr)?
+            .run();
+        others.push(server);
+        info!("serving metrics on {}", addr);
+    }
 
     if let Some(c) = config.ssl_conf {
-        let ssl = ssl_builder(c.cert_pem.as_str(), c.key_pem.as_str())?;
-        if let Some(le) = c.lets_encrypt {
-            // TODO: Let's encrypt
-            dbg!(&le);
+        let acme = c.lets_encrypt.as_ref().map(|le| Acme::new(le, &c));
+        let challenges = web::Data::new(acme.as_ref().map(Acme::challenges).unwrap_or_default());
+
+        if let Some(addr) = c.redirect_listen {
+            let https_port = server_conf.listen.first().map_or(443, |a| a.port());
+            let target = web::Data::new(HttpsRedirect::new(c.redirect_to.clone(), https_port));
+            let challenges = challenges.clone();
+            let server = HttpServer::new(move || {
+                App::new()
+                    .app_data(challenges.clone())
+                    .app_data(target.clone())
+                    .configure(configure_https_redirect)
+            })
+            .workers(1)
+            .disable_signals()
+            .shutdown_timeout(server_conf.shutdown_timeout)
+            .bind(addr)?
+            .run();
+            others.push(server);
         }
-        server = server.bind_openssl("0.0.0.0:8000", ssl)?;
-    } else {
-        server = server.bind("0.0.0.0:8000")?;
-    };
 
-    server.run().await.map_err(|c| c.into())
+        if let (Some(le), Some(acme)) = (&c.lets_encrypt, &acme) {
+            // The redirect listener answers the challenges when it shares the address
+            if c.redirect_listen != Some(le.challenge_listen) {
+                let server = HttpServer::new(move || {
+                    App::new()
+                        .app_data(challenges.clone())
+                        .configure(configure_acme_challenge)
+                })
+                .workers(1)
+                .disable_signals()
+                .shutdown_timeout(server_conf.shutdown_timeout)
+                .bind(le.challenge_listen)?
+                .run();
+                others.push(server);
+            }
+
+            match acme.ensure_certificate().await {
+                Ok(true) => info!("issued a new certificate for {}", le.domain),
+                Ok(false) => {}
+                Err(e) if acme.has_usable_certificate() => warn!(
+                    "could not renew the certificate for {}, serving the current one until the next attempt: {}",
+                    le.domain, e
+                ),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let cert = ReloadableCert::load(&c)?;
+        if let Some(acme) = acme {
+            actix_rt::spawn(acme.renew_forever(cert.clone()));
+        }
+        let certificates = c.sni.iter().map(|b| b.cert_pem.clone());
+        metrics::global().watch_certificates(
+            std::iter::once(c.cert_pem.clone())
+                .chain(certificates)
+                .collect(),
+        );
+        let server = https_server(app, &server_conf, &cert)?;
+        info!("listening on {} (https)", addresses(&server_conf));
+
+        if c.watch_interval > 0 {
+            actix_rt::spawn(cert.clone().watch(Duration::from_secs(c.watch_interval)));
+        }
+        actix_rt::spawn(async move {
+            if let Err(e) = cert.reload_on_sighup().await {
+                error!("certificates will not reload on SIGHUP: {}", e);
+            }
+        });
+
+        return shutdown::serve(server, others, &server_conf, &readiness, mongo).await;
+    }
+
+    let mut server = HttpServer::new(app)
+        .backlog(server_conf.backlog)
+        .keep_alive(server_conf.keep_alive)
+        .client_timeout(server_conf.client_timeout)
+        .client_shutdown(server_conf.client_shutdown)
+        .disable_signals()
+        .shutdown_timeout(server_conf.shutdown_timeout);
+
+    if let Some(workers) = server_conf.workers {
+        server = server.workers(workers);
+    }
+    for addr in &server_conf.listen {
+        server = server.bind(addr)?;
+    }
+
+    info!("listening on {} (http)", addresses(&server_conf));
+    shutdown::serve(server.run(), others, &server_conf, &readiness, mongo).await
+}
+
+fn addresses(conf: &ServerConf) -> String {
+    let addrs: Vec<String> = conf.listen.iter().map(|a| a.to_string()).collect();
+    addrs.join(", ")
 }
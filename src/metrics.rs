@@ -0,0 +1,273 @@
+use crate::acme::days_until_expiry;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+/// The process wide registry everything reports to
+pub fn global() -> &'static Registry {
+    REGISTRY.get_or_init(Registry::default)
+}
+
+/// Request, Mongo and certificate metrics, rendered in the Prometheus text
+/// exposition format
+#[derive(Debug, Default)]
+pub struct Registry {
+    requests: Mutex<BTreeMap<(String, String, u16), Histogram>>,
+    in_flight: AtomicI64,
+    mongo: Mutex<BTreeMap<(String, String), Histogram>>,
+    certificates: Mutex<Vec<String>>,
+}
+
+impl Registry {
+    /// Count a finished request, `route` is the matched pattern, not the path
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, latency: Duration) {
+        let key = (method.to_string(), route.to_string(), status);
+        let mut requests = self.requests.lock().unwrap();
+        requests.entry(key).or_default().observe(latency);
+    }
+
+    /// Track a request until the returned guard is dropped
+    pub fn in_flight(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(&self.in_flight)
+    }
+
+    /// Time of a Mongo operation of the `DocumentService` called `service`
+    pub fn observe_mongo(&self, service: &str, operation: &str, latency: Duration) {
+        let key = (service.to_string(), operation.to_string());
+        let mut mongo = self.mongo.lock().unwrap();
+        mongo.entry(key).or_default().observe(latency);
+    }
+
+    /// Certificate files whose expiry is reported, read on every scrape so
+    /// renewals and reloads show up
+    pub fn watch_certificates(&self, files: Vec<String>) {
+        *self.certificates.lock().unwrap() = files;
+    }
+
+    /// Everything in the Prometheus text format, version 0.0.4
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "http_requests_total",
+            "counter",
+            "Requests handled, by method, route and status",
+        );
+        let requests = self.requests.lock().unwrap();
+        for ((method, route, status), h) in requests.iter() {
+            let labels = labels(&[
+                ("method", method),
+                ("route", route),
+                ("status", &status.to_string()),
+            ]);
+            let _ = writeln!(out, "http_requests_total{{{}}} {}", labels, h.count);
+        }
+        header(
+            &mut out,
+            "http_request_duration_seconds",
+            "histogram",
+            "Request latency, by method, route and status",
+        );
+        for ((method, route, status), h) in requests.iter() {
+            let labels = labels(&[
+                ("method", method),
+                ("route", route),
+                ("status", &status.to_string()),
+            ]);
+            h.render(&mut out, "http_request_duration_seconds", &labels);
+        }
+        drop(requests);
+
+        header(
+            &mut out,
+            "http_requests_in_flight",
+            "gauge",
+            "Requests being handled",
+        );
+        let in_flight = self.in_flight.load(Ordering::Relaxed);
+        let _ = writeln!(out, "http_requests_in_flight {}", in_flight);
+
+        header(
+            &mut out,
+            "mongo_operation_duration_seconds",
+            "histogram",
+            "Mongo operation latency, by service and operation",
+        );
+        for ((service, operation), h) in self.mongo.lock().unwrap().iter() {
+            let labels = labels(&[("service", service), ("operation", operation)]);
+            h.render(&mut out, "mongo_operation_duration_seconds", &labels);
+        }
+
+        header(
+            &mut out,
+            "tls_certificate_expiry_days",
+            "gauge",
+            "Whole days until the certificate expires, negative once expired",
+        );
+        for file in self.certificates.lock().unwrap().iter() {
+            // Generated certificates that only live in memory have no file
+            if let Ok(days) = days_until_expiry(Path::new(file)) {
+                let labels = labels(&[("file", file)]);
+                let _ = writeln!(out, "tls_certificate_expiry_days{{{}}} {}", labels, days);
+            }
+        }
+
+        out
+    }
+}
+
+/// Decrements the in-flight gauge when the request is done, or dropped
+#[derive(Debug)]
+pub struct InFlight<'a>(&'a AtomicI64);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    /// Observations per bucket, not cumulative
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: vec![0; BUCKETS.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, latency: Duration) {
+        let secs = latency.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|le| secs <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (le, n) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += n;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, le, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, self.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let escaped: Vec<String> = pairs
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", k, v)
+        })
+        .collect();
+    escaped.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn renders_requests_and_mongo() {
+        let registry = Registry::default();
+        registry.observe_request("GET", "/api/posts/{id}", 200, Duration::from_millis(3));
+        registry.observe_request("GET", "/api/posts/{id}", 200, Duration::from_millis(30));
+        registry.observe_mongo("post", "find_one", Duration::from_secs(20));
+        let guard = registry.in_flight();
+
+        let out = registry.render();
+        let labels = "method=\"GET\",route=\"/api/posts/{id}\",status=\"200\"";
+        for line in &[
+            format!("http_requests_total{{{}}} 2", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"0.005\"}} 1", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"0.025\"}} 1", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"0.05\"}} 2", labels),
+            format!("http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2", labels),
+            format!("http_request_duration_seconds_sum{{{}}} 0.033", labels),
+            format!("http_request_duration_seconds_count{{{}}} 2", labels),
+            "http_requests_in_flight 1".to_string(),
+            "mongo_operation_duration_seconds_bucket{service=\"post\",operation=\"find_one\",le=\"10\"} 0".to_string(),
+            "mongo_operation_duration_seconds_bucket{service=\"post\",operation=\"find_one\",le=\"+Inf\"} 1".to_string(),
+            "# TYPE mongo_operation_duration_seconds histogram".to_string(),
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing {}\n{}", line, out);
+        }
+
+        drop(guard);
+        assert!(registry.render().contains("http_requests_in_flight 0\n"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(
+            labels(&[("file", "a\"b\\c\nd")]),
+            "file=\"a\\\"b\\\\c\\nd\""
+        );
+    }
+
+    #[test]
+    fn certificate_expiry() {
+        let dir = TestDir::new("metrics_cert");
+        let conf = crate::SSLConf {
+            cert_pem: dir.join("cert.pem").display().to_string(),
+            key_pem: dir.join("key.pem").display().to_string(),
+            ..crate::SSLConf::default()
+        };
+        crate::tls::SelfSigned::generate("metrics.test")
+            .unwrap()
+            .write(&conf)
+            .unwrap();
+
+        let registry = Registry::default();
+        registry.watch_certificates(vec![conf.cert_pem.clone(), "missing.pem".to_string()]);
+        let out = registry.render();
+        let expected = format!("tls_certificate_expiry_days{{file=\"{}\"}} ", conf.cert_pem);
+        let line = out.lines().find(|l| l.starts_with(&expected)).unwrap();
+        let days: i32 = line[expected.len()..].parse().unwrap();
+        assert!((364..=365).contains(&days), "{}", line);
+        assert!(!out.contains("missing.pem"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_service::{Service, Transform};
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::Error;
+use futures_util::future::{ok, LocalBoxFuture, Ready};
+use log::Level;
+use serde_json::Value;
+
+use crate::logging;
+
+/// Target of the access log records, so they can be told apart from the
+/// application's own records
+pub const ACCESS_LOG_TARGET: &str = "access";
+
+/// Logs method, path, status, latency and response size of every request at
+/// info level. Wrap `RequestIds` around it so the entries carry the id.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccessLog;
+
+impl<S, B> Transform<S> for AccessLog
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AccessLogMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(AccessLogMiddleware { service })
+    }
+}
+
+#[derive(Debug)]
+pub struct AccessLogMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for AccessLogMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            let (status, bytes) = match &res {
+                Ok(res) => {
+                    let bytes = match res.response().body().size() {
+                        BodySize::Sized(n) => Value::from(n),
+                        BodySize::Sized64(n) => Value::from(n),
+                        BodySize::None | BodySize::Empty => Value::from(0),
+                        BodySize::Stream => Value::Null,
+                    };
+                    (res.status(), bytes)
+                }
+                Err(e) => (e.as_response_error().status_code(), Value::Null),
+            };
+            let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+            logging::write(
+                Level::Info,
+                ACCESS_LOG_TARGET,
+                format_args!("request completed"),
+                &[
+                    ("method", method.into()),
+                    ("path", path.into()),
+                    ("status", status.as_u16().into()),
+                    (
+                        "latency_ms",
+                        ((latency_ms * 1000.0).round() / 1000.0).into(),
+                    ),
+                    ("bytes", bytes),
+                ],
+            );
+            res
+        })
+    }
+}
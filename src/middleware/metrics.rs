@@ -0,0 +1,143 @@
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use actix_service::{Service, Transform};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{Error, HttpRequest};
+use futures_util::future::{ok, LocalBoxFuture, Ready};
+
+use crate::metrics;
+
+/// Route label of requests no resource matched, so unknown paths don't
+/// create a series each
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Counts requests and their latency per method, route and status, and the
+/// requests in flight, in the global `metrics` registry
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestMetrics;
+
+impl<S, B> Transform<S> for RequestMetrics
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestMetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestMetricsMiddleware { service })
+    }
+}
+
+#[derive(Debug)]
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestMetricsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let in_flight = metrics::global().in_flight();
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            let status = res.status().as_u16();
+            let route = route(res.request(), status);
+            metrics::global().observe_request(&method, &route, status, started.elapsed());
+            drop(in_flight);
+            Ok(res)
+        })
+    }
+}
+
+/// The matched pattern of a request, rebuilt from the path and the matched
+/// segments: `/api/posts/5f1d...` becomes `/api/posts/{id}`. A 404 without
+/// matched segments came from a default service.
+///
+/// The segments are decoded, so they are looked up in the decoded path. A
+/// request path never becomes a label, that would add a series per path.
+fn route(req: &HttpRequest, status: u16) -> String {
+    let info = req.match_info();
+    if status == 404 && info.is_empty() {
+        return UNMATCHED_ROUTE.to_string();
+    }
+    let mut route = info.get_ref().path().to_string();
+    for (name, value) in info.iter() {
+        match route.rfind(value).filter(|_| !value.is_empty()) {
+            Some(at) => route.replace_range(at..at + value.len(), &format!("{{{}}}", name)),
+            None => return UNMATCHED_ROUTE.to_string(),
+        }
+    }
+    route
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn ok_response() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_rt::test]
+    async fn labels_requests_by_route() {
+        let mut app = test::init_service(
+            App::new().wrap(RequestMetrics).service(
+                web::scope("/metrics-test")
+                    .route("", web::get().to(ok_response))
+                    .route("/posts/{id}", web::get().to(ok_response))
+                    .route("/{tail:.*}", web::get().to(ok_response)),
+            ),
+        )
+        .await;
+        for uri in &[
+            "/metrics-test",
+            "/metrics-test/posts/5f1d7a3e9c1b2a0012345678",
+            "/metrics-test/posts/other",
+            "/metrics-test/posts/caf%C3%A9%20%7Bau%20lait%7D",
+            "/metrics-test/a/b",
+            "/metrics-test/a%2Fb/c%20d",
+            "/elsewhere",
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&mut app, req).await;
+        }
+
+        let out = metrics::global().render();
+        for line in &[
+            "http_requests_total{method=\"GET\",route=\"/metrics-test\",status=\"200\"} 1",
+            "http_requests_total{method=\"GET\",route=\"/metrics-test/posts/{id}\",status=\"200\"} 3",
+            "http_requests_total{method=\"GET\",route=\"/metrics-test/{tail}\",status=\"200\"} 2",
+            "http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1",
+        ] {
+            assert!(out.lines().any(|l| l == *line), "missing {}\n{}", line, out);
+        }
+        assert!(
+            !out.contains("caf"),
+            "a request path became a route\n{}",
+            out
+        );
+    }
+}
@@ -0,0 +1,9 @@
+mod access_log;
+mod metrics;
+mod problem;
+mod request_id;
+
+pub use access_log::{AccessLog, ACCESS_LOG_TARGET};
+pub use metrics::{RequestMetrics, UNMATCHED_ROUTE};
+pub use problem::ProblemDetails;
+pub use request_id::{RequestId, RequestIds, REQUEST_ID_HEADER};
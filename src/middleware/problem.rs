@@ -0,0 +1,250 @@
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::body::{Body, BodySize, MessageBody, ResponseBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, HeaderValue, StatusCode};
+use actix_web::{Error, HttpMessage};
+use futures_util::future::{ok, LocalBoxFuture, Ready};
+
+use super::RequestId;
+use crate::error::AppError;
+
+/// Fills in the request specific members of problem details bodies.
+///
+/// `ResponseError` renders an `AppError` without access to the request, so
+/// the `instance` is added here by re-rendering the error the response carries.
+/// Errors from actix's JSON, query and path extractors are rendered as
+/// `AppError::BadRequest` on the way, the empty 405 actix answers a method a
+/// resource has no route for becomes `AppError::MethodNotAllowed`, and the
+/// `RequestId` is added when `RequestIds` runs before this middleware.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProblemDetails;
+
+impl<S, B> Transform<S> for ProblemDetails
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ProblemDetailsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(ProblemDetailsMiddleware { service })
+    }
+}
+
+#[derive(Debug)]
+pub struct ProblemDetailsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for ProblemDetailsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let instance = req.path().to_string();
+        let method = req.method().to_string();
+        let request_id = req
+            .extensions()
+            .get::<RequestId>()
+            .map(RequestId::to_string);
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            let error = res.response().error();
+            let mut problem = match error.and_then(|e| e.as_error::<AppError>()) {
+                Some(e) => e.problem(),
+                None => match error.and_then(AppError::from_extractor) {
+                    Some(e) => e.problem(),
+                    None if error.is_none() && bodyless_405(&res) => {
+                        AppError::MethodNotAllowed(method).problem()
+                    }
+                    None => return Ok(res),
+                },
+            };
+            problem.instance = Some(instance);
+            problem.request_id = request_id;
+            let status = StatusCode::from_u16(problem.status).unwrap_or(res.status());
+            let body = serde_json::to_string(&problem).unwrap_or_default();
+            Ok(res.map_body(|head, _| {
+                head.status = status;
+                head.headers.insert(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/problem+json"),
+                );
+                ResponseBody::Other(Body::from(body))
+            }))
+        })
+    }
+}
+
+fn bodyless_405<B: MessageBody>(res: &ServiceResponse<B>) -> bool {
+    res.status() == StatusCode::METHOD_NOT_ALLOWED
+        && matches!(
+            res.response().body().size(),
+            BodySize::None | BodySize::Empty | BodySize::Sized(0) | BodySize::Sized64(0)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{FieldError, Problem, PROBLEM_TYPE_PREFIX};
+    use actix_web::{test, web, App};
+
+    async fn missing() -> Result<&'static str, AppError> {
+        Err(AppError::NotFound("post 42".to_string()))
+    }
+
+    async fn invalid() -> Result<&'static str, AppError> {
+        Err(AppError::ValidationError(vec![FieldError::new(
+            "title",
+            "must not be empty",
+        )]))
+    }
+
+    async fn fine() -> &'static str {
+        "ok"
+    }
+
+    async fn echo(body: web::Json<FieldError>, suffix: web::Data<String>) -> String {
+        format!("{}{}", body.field, suffix.get_ref())
+    }
+
+    async fn problem_for(path: &str) -> (StatusCode, Option<String>, Problem) {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(ProblemDetails)
+                .route("/posts/42", web::get().to(missing))
+                .route("/posts", web::post().to(invalid)),
+        )
+        .await;
+        let method = if path == "/posts" { "POST" } else { "GET" };
+        let req = test::TestRequest::with_uri(path)
+            .method(method.parse().unwrap())
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        let status = res.status();
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = test::read_body(res).await;
+        (status, content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[actix_rt::test]
+    async fn adds_the_request_path_as_instance() {
+        let (status, content_type, problem) = problem_for("/posts/42").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type.as_deref(), Some("application/problem+json"));
+        assert_eq!(problem.kind, format!("{}not_found", PROBLEM_TYPE_PREFIX));
+        assert_eq!(problem.status, 404);
+        assert_eq!(problem.code, "not_found");
+        assert_eq!(problem.instance.as_deref(), Some("/posts/42"));
+    }
+
+    #[actix_rt::test]
+    async fn keeps_field_errors() {
+        let (status, _, problem) = problem_for("/posts").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(problem.code, "validation_failed");
+        assert_eq!(
+            problem.errors,
+            vec![FieldError::new("title", "must not be empty")]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn renders_methods_without_a_route() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(ProblemDetails)
+                .service(web::resource("/").route(web::get().to(fine))),
+        )
+        .await;
+        let req = test::TestRequest::with_uri("/")
+            .method("PATCH".parse().unwrap())
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let problem: Problem = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(problem.code, "method_not_allowed");
+        assert_eq!(problem.detail, "PATCH is not allowed on this resource");
+        assert_eq!(problem.instance.as_deref(), Some("/"));
+    }
+
+    #[actix_rt::test]
+    async fn leaves_other_responses_alone() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(ProblemDetails)
+                .route("/", web::get().to(fine)),
+        )
+        .await;
+        let res =
+            test::call_service(&mut app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "ok");
+    }
+
+    #[actix_rt::test]
+    async fn renders_extractor_errors() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(ProblemDetails)
+                .data("!".to_string())
+                .route("/echo", web::post().to(echo)),
+        )
+        .await;
+        let req = |body: &'static str| {
+            test::TestRequest::post()
+                .uri("/echo")
+                .header(header::CONTENT_TYPE, "application/json")
+                .set_payload(body)
+                .to_request()
+        };
+
+        let res = test::call_service(&mut app, req("{\"field\": 1}")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let problem: Problem = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(problem.code, "bad_request");
+        assert!(
+            problem.detail.starts_with("invalid JSON body: "),
+            "{}",
+            problem.detail
+        );
+        assert_eq!(problem.instance.as_deref(), Some("/echo"));
+
+        let body = "{\"field\": \"a\", \"message\": \"b\"}";
+        let res = test::call_service(&mut app, req(body)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, "a!");
+    }
+}
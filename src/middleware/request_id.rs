@@ -0,0 +1,210 @@
+use std::fmt;
+use std::task::{Context, Poll};
+
+use actix_service::{Service, Transform};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::{HeaderName, HeaderValue};
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::{ok, LocalBoxFuture, Ready};
+use uuid::Uuid;
+
+/// Header the id is read from and echoed in
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest id accepted from a client
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// Correlation id of a request, shared by its logs and error bodies.
+///
+/// Handlers can extract it, anything running on the request's task, like the
+/// `DocumentService` operations, can get it through `RequestId::current`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    fn generate() -> Self {
+        RequestId(Uuid::new_v4().to_string())
+    }
+
+    /// Client supplied ids are kept when they are short and only use
+    /// characters that are safe to log and echo
+    fn parse(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c));
+        if valid {
+            Some(RequestId(value.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// The id of the request the current task is serving
+    pub fn current() -> Option<RequestId> {
+        CURRENT.try_with(RequestId::clone).ok()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let id = req.extensions().get::<RequestId>().cloned();
+        ok(id.unwrap_or_else(RequestId::generate))
+    }
+}
+
+/// Accepts or generates an `X-Request-Id` for every request and echoes it in
+/// the response. Wrap it around `ProblemDetails` and `AccessLog` so error
+/// bodies and access log entries carry it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestIds;
+
+impl<S, B> Transform<S> for RequestIds
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestIdsMiddleware { service })
+    }
+}
+
+#[derive(Debug)]
+pub struct RequestIdsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestIdsMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(RequestId::parse)
+            .unwrap_or_else(RequestId::generate);
+        req.extensions_mut().insert(id.clone());
+        let header = HeaderValue::from_str(id.as_str());
+        let fut = CURRENT.scope(id, self.service.call(req));
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Ok(value) = header {
+                res.headers_mut()
+                    .insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::AppError, error::Problem, middleware::ProblemDetails};
+    use actix_web::{test, web, App};
+
+    async fn echo(id: RequestId) -> String {
+        assert_eq!(RequestId::current(), Some(id.clone()));
+        id.to_string()
+    }
+
+    async fn fail() -> Result<String, AppError> {
+        Err(AppError::NotFound("nothing here".to_string()))
+    }
+
+    async fn call(id: Option<&str>, path: &str) -> (String, web::Bytes) {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(ProblemDetails)
+                .wrap(RequestIds)
+                .route("/echo", web::get().to(echo))
+                .route("/fail", web::get().to(fail)),
+        )
+        .await;
+        let mut req = test::TestRequest::get().uri(path);
+        if let Some(id) = id {
+            req = req.header(REQUEST_ID_HEADER, id);
+        }
+        let res = test::call_service(&mut app, req.to_request()).await;
+        let header = res.headers().get(REQUEST_ID_HEADER).unwrap();
+        let header = header.to_str().unwrap().to_string();
+        (header, test::read_body(res).await)
+    }
+
+    #[actix_rt::test]
+    async fn keeps_a_valid_id() {
+        let (header, body) = call(Some("abc-123_x.y:z"), "/echo").await;
+        assert_eq!(header, "abc-123_x.y:z");
+        assert_eq!(body, "abc-123_x.y:z");
+    }
+
+    #[actix_rt::test]
+    async fn replaces_a_missing_or_invalid_id() {
+        let long = "a".repeat(MAX_LEN + 1);
+        for id in &[
+            None,
+            Some(""),
+            Some("has space"),
+            Some("new\"line"),
+            Some(&long),
+        ] {
+            let (header, body) = call(*id, "/echo").await;
+            assert!(Uuid::parse_str(&header).is_ok(), "{:?} -> {}", id, header);
+            assert_eq!(body, header);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn adds_the_id_to_error_bodies() {
+        let (header, body) = call(Some("req-1"), "/fail").await;
+        assert_eq!(header, "req-1");
+        let problem: Problem = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.request_id.as_deref(), Some("req-1"));
+        assert_eq!(problem.instance.as_deref(), Some("/fail"));
+    }
+
+    #[test]
+    fn no_current_id_outside_requests() {
+        assert_eq!(RequestId::current(), None);
+    }
+}
@@ -1,14 +1,18 @@
 pub mod post_service;
 use super::Result;
-use crate::{error::AppError, mongo::Mongo};
+use crate::{error::AppError, logging, metrics, mongo::Mongo};
 use async_trait::async_trait;
 use futures_util::stream::StreamExt;
+use log::Level;
 use mongodb::{
     bson::{doc, Document},
     options::{FindOptions, UpdateModifications},
     Collection,
 };
 use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::future::Future;
+use std::time::Instant;
 use Into;
 
 /// Specify a dto object.
@@ -21,17 +25,28 @@ pub trait Dto {
 /// This should generate a standard query object
 ///
 /// TODO: Make is more general, Not only a single string
-pub trait Query {
-    fn from_string_id(id: String) -> Self;
+pub trait Query: Sized {
+    /// Fails with `AppError::BadRequest` when the id isn't a valid id, an
+    /// unparsable id must never turn into an empty filter
+    fn from_string_id(id: String) -> Result<Self>;
 }
 
 /// This represents a service for a document
 /// It will set up Get one/Get many/Put/Post/delete as a standard for a document
+///
+/// Documents and queries convert with `TryInto`, so a query the client sent
+/// fails the operation instead of turning into a broader filter.
 #[async_trait]
 pub trait DocumentService<T>
 where
-    T: From<Document> + Into<Document> + Into<UpdateModifications> + Send + Sync + Clone + Dto,
-    Self::Query: Into<Document> + Into<Option<Document>> + Send + Clone + Serialize + Sync + Query,
+    T: From<Document>
+        + TryInto<Document, Error = AppError>
+        + TryInto<UpdateModifications, Error = AppError>
+        + Send
+        + Sync
+        + Clone
+        + Dto,
+    Self::Query: TryInto<Document, Error = AppError> + Send + Clone + Serialize + Sync + Query,
 {
     type Query;
 
@@ -48,7 +63,14 @@ where
     where
         T: 'a,
     {
-        match self.collection().find_one(query.clone(), None).await? {
+        let filter: Document = query.clone().try_into()?;
+        let found = timed(
+            self.name(),
+            "find_one",
+            self.collection().find_one(filter, None),
+        )
+        .await?;
+        match found {
             Some(t) => Ok(t.into()),
             None => Err(not_found(self.name(), &query)?),
         }
@@ -60,12 +82,17 @@ where
         T: 'a,
         Self::Query: 'a,
     {
-        let mut cursor = self.collection().find(query, pagination).await?;
+        let filter: Document = query.try_into()?;
+        let results = timed(self.name(), "find", async {
+            let mut cursor = self.collection().find(filter, pagination).await?;
 
-        let mut results: Vec<T> = Vec::new();
-        while let Some(x) = cursor.next().await {
-            results.push(x?.into())
-        }
+            let mut results: Vec<T> = Vec::new();
+            while let Some(x) = cursor.next().await {
+                results.push(x?.into())
+            }
+            Ok(results)
+        })
+        .await?;
 
         Ok(results)
     }
@@ -74,10 +101,13 @@ where
     where
         T: 'a,
     {
-        let result = self
-            .collection()
-            .delete_one(query.clone().into(), None)
-            .await?;
+        let filter: Document = query.clone().try_into()?;
+        let result = timed(
+            self.name(),
+            "delete_one",
+            self.collection().delete_one(filter, None),
+        )
+        .await?;
         if result.deleted_count > 0 {
             Ok(())
         } else {
@@ -89,10 +119,13 @@ where
     where
         T: 'a,
     {
-        let res = self
-            .collection()
-            .insert_one(data.clone().into(), None)
-            .await?;
+        let document: Document = data.clone().try_into()?;
+        let res = timed(
+            self.name(),
+            "insert_one",
+            self.collection().insert_one(document, None),
+        )
+        .await?;
 
         let mut result = data;
         res.inserted_id.as_str();
@@ -110,14 +143,14 @@ where
     where
         T: 'a,
     {
-        let result = self
-            .collection()
-            .update_one(
-                query.clone().into(),
-                data, //TODO: Cannot use into here but still works, why?
-                None,
-            )
-            .await?;
+        let filter: Document = query.clone().try_into()?;
+        let update: UpdateModifications = data.try_into()?;
+        let result = timed(
+            self.name(),
+            "update_one",
+            self.collection().update_one(filter, update, None),
+        )
+        .await?;
 
         // Possible bug (TODO)
         // Okay to use the same query here if top Query is only using _id
@@ -130,6 +163,34 @@ where
     }
 }
 
+/// Awaits a Mongo operation of the service `name`, logging how long it took
+/// at debug level and recording it in the metrics. Runs on the request's task, so the entry carries its id.
+async fn timed<F, T>(name: &str, operation: &'static str, fut: F) -> mongodb::error::Result<T>
+where
+    F: Future<Output = mongodb::error::Result<T>>,
+{
+    let started = Instant::now();
+    let result = fut.await;
+    let elapsed = started.elapsed();
+    metrics::global().observe_mongo(name, operation, elapsed);
+    let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+    logging::write(
+        Level::Debug,
+        module_path!(),
+        format_args!("mongo {} on {}", operation, name),
+        &[
+            ("service", name.into()),
+            ("operation", operation.into()),
+            (
+                "elapsed_ms",
+                ((elapsed_ms * 1000.0).round() / 1000.0).into(),
+            ),
+            ("ok", result.is_ok().into()),
+        ],
+    );
+    result
+}
+
 fn not_found<T>(name: &str, query: &T) -> Result<AppError>
 where
     T: Serialize,
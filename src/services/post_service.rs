@@ -1,7 +1,7 @@
 use super::{DocumentService, Dto, Query};
-use crate::{documents::Post, mongo::Mongo};
+use crate::{documents::Post, mongo::Mongo, Result};
 use async_trait::async_trait;
-use mongodb::{bson::Document, Collection};
+use mongodb::Collection;
 
 pub struct PostService {
     col: Collection,
@@ -26,19 +26,14 @@ impl DocumentService<Post> for PostService {
     }
 }
 
-impl From<Post> for Option<Document> {
-    fn from(post: Post) -> Self {
-        Some(post.into())
-    }
-}
-
 impl Query for Post {
-    fn from_string_id(id: String) -> Self {
-        Post {
+    fn from_string_id(id: String) -> Result<Self> {
+        Mongo::to_object_id(&id)?;
+        Ok(Post {
             id: Some(id),
             name: None,
             author: None,
-        }
+        })
     }
 }
 
@@ -47,3 +42,23 @@ impl Dto for Post {
         self.id = Some(id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+
+    #[test]
+    fn from_string_id_rejects_invalid_ids() {
+        for id in &["not-an-id", "", "5f1d7a3e9c1b2a0012345678z"] {
+            match Post::from_string_id(id.to_string()) {
+                Err(AppError::BadRequest(m)) => {
+                    assert_eq!(m, format!("'{}' is not a valid id", id))
+                }
+                other => panic!("{}: {:?}", id, other.map(|p| p.id)),
+            }
+        }
+        let post = Post::from_string_id("5f1d7a3e9c1b2a0012345678".to_string()).unwrap();
+        assert_eq!(post.id.as_deref(), Some("5f1d7a3e9c1b2a0012345678"));
+    }
+}
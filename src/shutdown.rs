@@ -0,0 +1,85 @@
+use crate::config::ServerConf;
+use crate::health::Health;
+use crate::mongo::Mongo;
+use crate::Result;
+use actix_rt::time::delay_for;
+use actix_web::dev::Server;
+use futures_util::future::{join, join_all, select, Either};
+use log::info;
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Runs `server` until it stops or SIGTERM or SIGINT arrive, then shuts down
+/// gracefully: readiness reports down for `shutdown_delay` seconds, the
+/// listeners of `server` and `others` close and open connections get
+/// `shutdown_timeout` seconds to finish their requests.
+///
+/// Every server needs `disable_signals` and the same `shutdown_timeout`,
+/// otherwise actix stops them on its own.
+///
+/// Once the servers have stopped `mongo` is dropped, and that drop is the
+/// whole Mongo shutdown: the driver has no close, its pool disconnects when
+/// the last handle to the client is gone. The handles in the app factories
+/// actix keeps go with the process right after.
+pub async fn serve(
+    server: Server,
+    others: Vec<Server>,
+    conf: &ServerConf,
+    health: &Health,
+    mongo: Mongo,
+) -> Result<()> {
+    let name = match select(server.clone(), Box::pin(shutdown_signal())).await {
+        Either::Left((stopped, _)) => return stopped.map_err(|e| e.into()),
+        Either::Right((name, _)) => name?,
+    };
+    let started = Instant::now();
+    health.shut_down();
+    if conf.shutdown_delay > 0 {
+        info!(
+            "{} received, readiness reports down, closing the listeners in {}s",
+            name, conf.shutdown_delay
+        );
+        delay_for(Duration::from_secs(conf.shutdown_delay)).await;
+    } else {
+        info!("{} received, closing the listeners", name);
+    }
+
+    info!(
+        "draining in-flight requests for up to {}s",
+        conf.shutdown_timeout
+    );
+    let stopped = join(
+        server.stop(true),
+        join_all(others.iter().map(|s| s.stop(true))),
+    );
+    // Workers stop as soon as their connections are closed, or are dropped
+    // with them at the deadline, so stopping first means nothing was cut off
+    let deadline = delay_for(Duration::from_secs(conf.shutdown_timeout));
+    let drained = match select(Box::pin(stopped), Box::pin(deadline)).await {
+        Either::Left(_) => "all connections finished",
+        Either::Right((_, stopped)) => {
+            stopped.await;
+            "the drain deadline passed, connections still open were dropped"
+        }
+    };
+    drop(mongo);
+
+    info!(
+        "shutdown complete in {:.1}s: {}",
+        started.elapsed().as_secs_f64(),
+        drained
+    );
+    Ok(())
+}
+
+/// Name of the first termination signal received
+async fn shutdown_signal() -> io::Result<&'static str> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let name = match select(Box::pin(terminate.recv()), Box::pin(interrupt.recv())).await {
+        Either::Left(_) => "SIGTERM",
+        Either::Right(_) => "SIGINT",
+    };
+    Ok(name)
+}
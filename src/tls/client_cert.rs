@@ -0,0 +1,98 @@
+use crate::error::AppError;
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use futures_util::future::{ready, Ready};
+use openssl::{
+    ssl::SslRef,
+    x509::{X509NameRef, X509VerifyResult},
+};
+
+/// The verified certificate a client presented during the TLS handshake.
+///
+/// As an extractor it fails with `AppError::Forbidden` when the connection has
+/// no verified client certificate, use `Option<ClientCert>` when
+/// `ClientVerify::Optional` lets such clients in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientCert {
+    subject: String,
+}
+
+impl ClientCert {
+    /// The certificate of a connection, if the client sent one that verified
+    pub fn from_ssl(ssl: &SslRef) -> Option<Self> {
+        if ssl.verify_result() != X509VerifyResult::OK {
+            return None;
+        }
+        let cert = ssl.peer_certificate()?;
+        Some(ClientCert {
+            subject: one_line(cert.subject_name()),
+        })
+    }
+
+    /// Subject distinguished name in certificate order, e.g. `CN=client, O=Example`
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+}
+
+fn one_line(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|v| v.to_string())
+                .unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl FromRequest for ClientCert {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        // Stored once per connection by `https_server`
+        let cert = req.extensions().get::<Option<ClientCert>>().cloned();
+        ready(cert.flatten().ok_or(AppError::Forbidden))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use openssl::{nid::Nid, x509::X509NameBuilder};
+
+    #[test]
+    fn subject_in_certificate_order() {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "client").unwrap();
+        name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "Example")
+            .unwrap();
+        assert_eq!(one_line(&name.build()), "CN=client, O=Example");
+    }
+
+    #[actix_rt::test]
+    async fn extractor_needs_a_verified_certificate() {
+        let (req, mut payload) = TestRequest::default().to_http_parts();
+        assert!(ClientCert::from_request(&req, &mut payload).await.is_err());
+
+        let (req, mut payload) = TestRequest::default().to_http_parts();
+        req.extensions_mut().insert::<Option<ClientCert>>(None);
+        assert!(ClientCert::from_request(&req, &mut payload).await.is_err());
+
+        let cert = ClientCert {
+            subject: "CN=client".to_string(),
+        };
+        let (req, mut payload) = TestRequest::default().to_http_parts();
+        req.extensions_mut().insert(Some(cert.clone()));
+        assert_eq!(
+            ClientCert::from_request(&req, &mut payload).await.unwrap(),
+            cert
+        );
+    }
+}
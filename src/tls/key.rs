@@ -0,0 +1,143 @@
+use crate::{error::AppError, Passphrase, Result, SSLConf};
+use openssl::{
+    pkey::{PKey, Private},
+    symm::Cipher,
+};
+use std::fs;
+
+/// Read and, when a passphrase is configured, decrypt `conf.key_pem` in memory.
+/// An encrypted key without a passphrase fails instead of prompting on the terminal.
+pub(crate) fn private_key(conf: &SSLConf) -> Result<PKey<Private>> {
+    let pem = fs::read(&conf.key_pem)
+        .map_err(|e| AppError::SSLError(format!("could not read {}: {}", conf.key_pem, e)))?;
+
+    let key = match &conf.key_passphrase {
+        Some(passphrase) => {
+            let passphrase = resolve(passphrase)?;
+            PKey::private_key_from_pem_passphrase(&pem, passphrase.as_bytes())
+        }
+        None if is_encrypted(&pem) => {
+            return Err(AppError::SSLError(format!(
+                "{} is encrypted, but no passphrase is configured",
+                conf.key_pem
+            )))
+        }
+        None => PKey::private_key_from_pem_callback(&pem, |_| Ok(0)),
+    };
+    key.map_err(|e| AppError::SSLError(format!("could not load {}: {}", conf.key_pem, e)))
+}
+
+/// PEM for writing `key` to `conf.key_pem`, PKCS#8 encrypted with the
+/// configured passphrase when there is one, so it can be read back by `private_key`.
+pub(crate) fn private_key_pem(conf: &SSLConf, key: &PKey<Private>) -> Result<Vec<u8>> {
+    let pem = match passphrase(conf)? {
+        Some(passphrase) => {
+            key.private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), passphrase.as_bytes())?
+        }
+        None => key.private_key_to_pem_pkcs8()?,
+    };
+    Ok(pem)
+}
+
+/// The configured passphrase, read from wherever `conf.key_passphrase` points
+fn passphrase(conf: &SSLConf) -> Result<Option<String>> {
+    conf.key_passphrase.as_ref().map(resolve).transpose()
+}
+
+/// `ENCRYPTED PRIVATE KEY` for PKCS#8, `Proc-Type: 4,ENCRYPTED` for traditional keys
+fn is_encrypted(pem: &[u8]) -> bool {
+    pem.windows(9).any(|w| w == b"ENCRYPTED")
+}
+
+fn resolve(passphrase: &Passphrase) -> Result<String> {
+    match passphrase {
+        Passphrase::Value(value) => Ok(value.expose().clone()),
+        Passphrase::File(path) => {
+            let value = fs::read_to_string(path).map_err(|e| {
+                AppError::SSLError(format!("could not read passphrase file {}: {}", path, e))
+            })?;
+            Ok(value.trim_end_matches(&['\r', '\n'][..]).to_string())
+        }
+        Passphrase::Env(var) => std::env::var(var)
+            .map_err(|_| AppError::SSLError(format!("passphrase variable {} is not set", var))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use openssl::{
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        symm::Cipher,
+    };
+    use std::path::Path;
+
+    fn conf(key_pem: &Path, key_passphrase: Option<Passphrase>) -> SSLConf {
+        SSLConf {
+            key_pem: key_pem.display().to_string(),
+            key_passphrase,
+            ..SSLConf::default()
+        }
+    }
+
+    #[test]
+    fn decrypts_pkcs8_and_traditional_keys() {
+        let dir = TestDir::new("tls_key");
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ec = EcKey::generate(&group).unwrap();
+        let key = PKey::from_ec_key(ec.clone()).unwrap();
+
+        let pkcs8 = dir.join("pkcs8.pem");
+        fs::write(
+            &pkcs8,
+            key.private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), b"secret")
+                .unwrap(),
+        )
+        .unwrap();
+        let traditional = dir.join("traditional.pem");
+        fs::write(
+            &traditional,
+            ec.private_key_to_pem_passphrase(Cipher::aes_128_cbc(), b"secret")
+                .unwrap(),
+        )
+        .unwrap();
+        let passphrase_file = dir.join("passphrase");
+        fs::write(&passphrase_file, "secret\n").unwrap();
+        std::env::set_var("RUST_AT_ONE_TEST_KEY_PASSPHRASE", "secret");
+
+        for pem in &[&pkcs8, &traditional] {
+            for passphrase in [
+                Passphrase::Value("secret".to_string().into()),
+                Passphrase::File(passphrase_file.display().to_string()),
+                Passphrase::Env("RUST_AT_ONE_TEST_KEY_PASSPHRASE".to_string()),
+            ]
+            .iter()
+            {
+                let loaded = private_key(&conf(pem, Some(passphrase.clone()))).unwrap();
+                assert!(loaded.public_eq(&key));
+            }
+        }
+
+        let wrong = conf(&pkcs8, Some(Passphrase::Value("wrong".to_string().into())));
+        assert!(private_key(&wrong).is_err());
+        assert_eq!(
+            private_key(&conf(&traditional, None))
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "TLS: {} is encrypted, but no passphrase is configured",
+                traditional.display()
+            )
+        );
+        let unset = conf(
+            &pkcs8,
+            Some(Passphrase::Env("RUST_AT_ONE_UNSET".to_string())),
+        );
+        assert_eq!(
+            private_key(&unset).unwrap_err().to_string(),
+            "TLS: passphrase variable RUST_AT_ONE_UNSET is not set"
+        );
+    }
+}
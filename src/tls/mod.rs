@@ -0,0 +1,59 @@
+mod client_cert;
+pub(crate) mod key;
+mod policy;
+mod reload;
+mod self_signed;
+mod server;
+mod sni;
+
+pub use client_cert::ClientCert;
+pub use reload::ReloadableCert;
+pub use self_signed::SelfSigned;
+pub use server::https_server;
+
+use crate::{ClientVerify, Result, SSLConf};
+use openssl::{
+    ssl::{SslAcceptorBuilder, SslVerifyMode},
+    x509::X509Name,
+};
+use policy::policy_builder;
+
+// load ssl keys
+// to create a self-signed temporary cert for testing:
+// `openssl req -x509 -newkey rsa:4096 -keyout key.pem -out cert.pem -days 365 -subj '/CN=localhost'`
+// and set `ssl.key_passphrase` (or `-nodes` for an unencrypted key)
+pub fn ssl_builder(conf: &SSLConf) -> Result<SslAcceptorBuilder> {
+    let mut builder = policy_builder(conf)?;
+    let key = key::private_key(conf)?;
+    builder.set_private_key(&key)?;
+    builder.set_certificate_chain_file(&conf.cert_pem)?;
+    // Rejects a key that isn't the certificate's, e.g. while both are being replaced
+    builder.check_private_key()?;
+    client_auth(&mut builder, conf)?;
+    Ok(builder)
+}
+
+/// Like `ssl_builder`, with a generated certificate instead of `conf`'s files
+pub fn self_signed_builder(conf: &SSLConf, cert: &SelfSigned) -> Result<SslAcceptorBuilder> {
+    let mut builder = policy_builder(conf)?;
+    builder.set_private_key(&cert.key)?;
+    builder.set_certificate(&cert.cert)?;
+    client_auth(&mut builder, conf)?;
+    Ok(builder)
+}
+
+fn client_auth(builder: &mut SslAcceptorBuilder, conf: &SSLConf) -> Result<()> {
+    if let Some(ca) = &conf.client_ca {
+        builder.set_ca_file(ca)?;
+        // Sent in the certificate request so clients pick a matching certificate
+        builder.set_client_ca_list(X509Name::load_client_ca_file(ca)?);
+    }
+    match conf.client_verify {
+        ClientVerify::None => builder.set_verify(SslVerifyMode::NONE),
+        ClientVerify::Optional => builder.set_verify(SslVerifyMode::PEER),
+        ClientVerify::Required => {
+            builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT)
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use crate::{error::AppError, Alpn, Result, SSLConf, TlsProfile, TlsVersion};
+use openssl::ssl::{
+    select_next_proto, AlpnError, SslAcceptor, SslAcceptorBuilder, SslMethod, SslVersion,
+};
+
+/// An acceptor builder with the protocol versions, ciphers and ALPN from `conf`.
+/// Settings that contradict each other or the profile are rejected.
+pub(super) fn policy_builder(conf: &SSLConf) -> Result<SslAcceptorBuilder> {
+    let (lowest, highest) = match conf.profile {
+        TlsProfile::Intermediate => (TlsVersion::Tls1_2, TlsVersion::Tls1_3),
+        TlsProfile::Modern => (TlsVersion::Tls1_3, TlsVersion::Tls1_3),
+    };
+    let outside = |v: &TlsVersion| *v < lowest || *v > highest;
+    if let Some(version) = conf
+        .min_protocol
+        .iter()
+        .chain(&conf.max_protocol)
+        .find(|v| outside(v))
+    {
+        return Err(policy_error(format!(
+            "the {} profile only allows {} to {}, got {}",
+            conf.profile, lowest, highest, version
+        )));
+    }
+    let min = conf.min_protocol.unwrap_or(lowest);
+    let max = conf.max_protocol.unwrap_or(highest);
+    if min > max {
+        return Err(policy_error(format!(
+            "ssl.min_protocol {} is above ssl.max_protocol {}",
+            min, max
+        )));
+    }
+    if conf.ciphers.is_some() && min == TlsVersion::Tls1_3 {
+        return Err(policy_error(
+            "ssl.ciphers only applies to TLS 1.2, which is not enabled".to_string(),
+        ));
+    }
+    if conf.ciphersuites.is_some() && max == TlsVersion::Tls1_2 {
+        return Err(policy_error(
+            "ssl.ciphersuites only applies to TLS 1.3, which is not enabled".to_string(),
+        ));
+    }
+
+    let mut builder = match conf.profile {
+        TlsProfile::Intermediate => SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())?,
+        TlsProfile::Modern => SslAcceptor::mozilla_modern_v5(SslMethod::tls())?,
+    };
+    builder.set_min_proto_version(Some(ssl_version(min)))?;
+    builder.set_max_proto_version(Some(ssl_version(max)))?;
+    if let Some(ciphers) = &conf.ciphers {
+        builder.set_cipher_list(ciphers)?;
+    }
+    if let Some(ciphersuites) = &conf.ciphersuites {
+        builder.set_ciphersuites(ciphersuites)?;
+    }
+
+    let protos = alpn_protos(&conf.alpn);
+    builder.set_alpn_select_callback(move |_, client| {
+        // Our preference wins over the client's
+        select_next_proto(&protos, client).ok_or(AlpnError::NOACK)
+    });
+    Ok(builder)
+}
+
+fn policy_error(message: String) -> AppError {
+    AppError::SSLError(message)
+}
+
+fn ssl_version(version: TlsVersion) -> SslVersion {
+    match version {
+        TlsVersion::Tls1_2 => SslVersion::TLS1_2,
+        TlsVersion::Tls1_3 => SslVersion::TLS1_3,
+    }
+}
+
+/// Length prefixed protocol ids, the ALPN wire format
+fn alpn_protos(alpn: &[Alpn]) -> Vec<u8> {
+    let mut protos = Vec::new();
+    for proto in alpn {
+        protos.push(proto.id().len() as u8);
+        protos.extend_from_slice(proto.id().as_bytes());
+    }
+    protos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(conf: SSLConf) -> String {
+        match policy_builder(&conf) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("{:?} was accepted", conf),
+        }
+    }
+
+    #[test]
+    fn alpn_in_preference_order() {
+        assert_eq!(
+            alpn_protos(&[Alpn::H2, Alpn::Http11]),
+            b"\x02h2\x08http/1.1".to_vec()
+        );
+    }
+
+    #[test]
+    fn narrowing_the_profile() {
+        let conf = SSLConf {
+            min_protocol: Some(TlsVersion::Tls1_3),
+            ciphersuites: Some("TLS_AES_256_GCM_SHA384".to_string()),
+            ..SSLConf::default()
+        };
+        let mut builder = policy_builder(&conf).unwrap();
+        assert_eq!(builder.min_proto_version(), Some(SslVersion::TLS1_3));
+        assert_eq!(builder.max_proto_version(), Some(SslVersion::TLS1_3));
+    }
+
+    #[test]
+    fn invalid_combinations() {
+        assert_eq!(
+            rejected(SSLConf {
+                profile: TlsProfile::Modern,
+                max_protocol: Some(TlsVersion::Tls1_2),
+                ..SSLConf::default()
+            }),
+            "TLS: the modern profile only allows tls1.3 to tls1.3, got tls1.2"
+        );
+        assert_eq!(
+            rejected(SSLConf {
+                min_protocol: Some(TlsVersion::Tls1_3),
+                max_protocol: Some(TlsVersion::Tls1_2),
+                ..SSLConf::default()
+            }),
+            "TLS: ssl.min_protocol tls1.3 is above ssl.max_protocol tls1.2"
+        );
+        assert_eq!(
+            rejected(SSLConf {
+                profile: TlsProfile::Modern,
+                ciphers: Some("ECDHE-RSA-AES128-GCM-SHA256".to_string()),
+                ..SSLConf::default()
+            }),
+            "TLS: ssl.ciphers only applies to TLS 1.2, which is not enabled"
+        );
+        assert_eq!(
+            rejected(SSLConf {
+                max_protocol: Some(TlsVersion::Tls1_2),
+                ciphersuites: Some("TLS_AES_128_GCM_SHA256".to_string()),
+                ..SSLConf::default()
+            }),
+            "TLS: ssl.ciphersuites only applies to TLS 1.3, which is not enabled"
+        );
+        assert!(rejected(SSLConf {
+            ciphers: Some("NOT-A-CIPHER".to_string()),
+            ..SSLConf::default()
+        })
+        .starts_with("TLS: "));
+    }
+}
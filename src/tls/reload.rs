@@ -0,0 +1,267 @@
+use super::{
+    self_signed::{self, SelfSigned},
+    self_signed_builder,
+    sni::{self, Certificates},
+    ssl_builder,
+};
+use crate::{Result, SSLConf};
+use actix_rt::time::interval;
+use log::{error, info, warn};
+use openssl::ssl::{NameType, SniError, SslAcceptorBuilder};
+use std::{
+    fs,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// The certificates and keys from `SSLConf`, replaceable while the server runs.
+///
+/// Every acceptor from `acceptor` swaps in the current context for the SNI
+/// hostname during the handshake, so new connections use the latest
+/// certificate while open connections keep the one they started with.
+#[derive(Clone)]
+pub struct ReloadableCert {
+    conf: SSLConf,
+    /// Served instead of the missing default files in the development mode
+    generated: Option<Arc<SelfSigned>>,
+    current: Arc<RwLock<Certificates>>,
+}
+
+impl std::fmt::Debug for ReloadableCert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReloadableCert")
+            .field("cert_pem", &self.conf.cert_pem)
+            .field("key_pem", &self.conf.key_pem)
+            .finish()
+    }
+}
+
+impl ReloadableCert {
+    /// Load the certificate chains and keys, failing if any of them can't be used.
+    /// With `SSLConf::self_signed` and no key or certificate file a certificate
+    /// for localhost is generated instead.
+    pub fn load(conf: &SSLConf) -> Result<Self> {
+        let mut generated = None;
+        if self_signed::wanted(conf) {
+            let localhost = SelfSigned::localhost()?;
+            warn!(
+                "{} and {} don't exist, serving a generated self signed certificate for localhost",
+                conf.key_pem, conf.cert_pem
+            );
+            warn!("clients will not trust it, ssl.self_signed is for development only");
+            if conf.write_self_signed {
+                localhost.write(conf)?;
+                warn!(
+                    "wrote the self signed certificate to {} and {}",
+                    conf.cert_pem, conf.key_pem
+                );
+            } else {
+                generated = Some(Arc::new(localhost));
+            }
+        }
+
+        let certificates = Certificates::load(conf, generated.as_deref())?;
+        Ok(ReloadableCert {
+            conf: conf.clone(),
+            generated,
+            current: Arc::new(RwLock::new(certificates)),
+        })
+    }
+
+    /// The generated certificate, until the real files show up
+    fn generated(&self) -> Option<&SelfSigned> {
+        self.generated
+            .as_deref()
+            .filter(|_| self_signed::wanted(&self.conf))
+    }
+
+    /// An acceptor builder for one listener, sharing the reloadable context
+    pub fn acceptor(&self) -> Result<SslAcceptorBuilder> {
+        let mut builder = match self.generated() {
+            Some(generated) => self_signed_builder(&self.conf, generated)?,
+            None => ssl_builder(&self.conf)?,
+        };
+        let current = self.current.clone();
+        // OpenSSL calls this for every handshake, with or without SNI
+        builder.set_servername_callback(move |ssl, _alert| {
+            let certificates = current.read().map_err(|_| SniError::ALERT_FATAL)?;
+            let hostname = ssl.servername(NameType::HOST_NAME).map(|h| h.to_string());
+            ssl.set_ssl_context(certificates.for_host(hostname.as_deref()))
+                .map_err(|_| SniError::ALERT_FATAL)
+        });
+        Ok(builder)
+    }
+
+    /// Read the files again. On failure the previous certificates stay in use.
+    pub fn reload(&self) -> Result<()> {
+        let certificates = Certificates::load(&self.conf, self.generated())?;
+        *self.current.write().expect("certificate lock poisoned") = certificates;
+        Ok(())
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        let modified: Option<Vec<SystemTime>> = sni::files(&self.conf)
+            .into_iter()
+            .map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+            .collect();
+        modified?.into_iter().max()
+    }
+
+    fn try_reload(&self, reason: &str) -> bool {
+        match self.reload() {
+            Ok(_) => {
+                info!("reloaded certificate {} ({})", self.conf.cert_pem, reason);
+                true
+            }
+            Err(e) => {
+                error!(
+                    "could not reload certificate {} ({}), keeping the current one: {}",
+                    self.conf.cert_pem, reason, e
+                );
+                false
+            }
+        }
+    }
+
+    /// Reload on SIGHUP, for as long as the server runs
+    pub async fn reload_on_sighup(self) -> Result<()> {
+        let mut hangup = signal(SignalKind::hangup())?;
+        while hangup.recv().await.is_some() {
+            self.try_reload("SIGHUP");
+        }
+        Ok(())
+    }
+
+    /// Reload when any of the files change, checking every `every`.
+    /// A failed reload is retried on the next check.
+    pub async fn watch(self, every: Duration) {
+        let mut loaded = self.modified();
+        let mut ticks = interval(every);
+        loop {
+            ticks.tick().await;
+            let modified = self.modified();
+            if modified.is_some() && modified != loaded && self.try_reload("files changed") {
+                loaded = modified;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use crate::CertBinding;
+    use openssl::nid::Nid;
+    use std::path::Path;
+
+    fn write_self_signed(dir: &Path, cn: &str) -> (String, String) {
+        let conf = SSLConf {
+            cert_pem: dir.join("cert.pem").display().to_string(),
+            key_pem: dir.join("key.pem").display().to_string(),
+            ..SSLConf::default()
+        };
+        SelfSigned::generate(cn).unwrap().write(&conf).unwrap();
+        (conf.cert_pem, conf.key_pem)
+    }
+
+    fn current_cn(cert: &ReloadableCert) -> String {
+        served_cn(cert, None)
+    }
+
+    fn served_cn(cert: &ReloadableCert, hostname: Option<&str>) -> String {
+        let certificates = cert.current.read().unwrap();
+        let cn = certificates
+            .for_host(hostname)
+            .certificate()
+            .unwrap()
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .unwrap()
+            .data()
+            .as_utf8()
+            .unwrap()
+            .to_string();
+        cn
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_old_certificate() {
+        let dir = TestDir::new("tls_reload");
+        let (cert_pem, key_pem) = write_self_signed(&dir, "first");
+
+        let cert = ReloadableCert::load(&SSLConf {
+            cert_pem,
+            key_pem: key_pem.clone(),
+            ..SSLConf::default()
+        })
+        .unwrap();
+        assert_eq!(current_cn(&cert), "first");
+
+        fs::write(&key_pem, "not a key").unwrap();
+        assert!(cert.reload().is_err());
+        assert_eq!(current_cn(&cert), "first");
+
+        write_self_signed(&dir, "second");
+        assert!(cert.reload().is_ok());
+        assert_eq!(current_cn(&cert), "second");
+    }
+
+    #[test]
+    fn sni_picks_the_bound_certificate() {
+        let dir = TestDir::new("tls_sni");
+        for name in &["default", "api", "org"] {
+            fs::create_dir_all(dir.join(name)).unwrap();
+        }
+        let (cert_pem, key_pem) = write_self_signed(&dir.join("default"), "default");
+        let (api_cert, api_key) = write_self_signed(&dir.join("api"), "api");
+        let (org_cert, org_key) = write_self_signed(&dir.join("org"), "org");
+
+        let cert = ReloadableCert::load(&SSLConf {
+            cert_pem,
+            key_pem,
+            sni: vec![
+                CertBinding {
+                    hostname: "api.example.com".to_string(),
+                    cert_pem: api_cert,
+                    key_pem: api_key,
+                },
+                CertBinding {
+                    hostname: "*.example.org".to_string(),
+                    cert_pem: org_cert,
+                    key_pem: org_key,
+                },
+            ],
+            ..SSLConf::default()
+        })
+        .unwrap();
+
+        assert_eq!(served_cn(&cert, Some("API.example.com")), "api");
+        assert_eq!(served_cn(&cert, Some("www.example.org")), "org");
+        assert_eq!(served_cn(&cert, Some("a.b.example.org")), "default");
+        assert_eq!(served_cn(&cert, Some("example.com")), "default");
+        assert_eq!(served_cn(&cert, None), "default");
+    }
+
+    #[test]
+    fn generated_until_the_files_exist() {
+        let dir = TestDir::new("tls_generated");
+        let conf = SSLConf {
+            cert_pem: dir.join("cert.pem").display().to_string(),
+            key_pem: dir.join("key.pem").display().to_string(),
+            self_signed: true,
+            ..SSLConf::default()
+        };
+
+        let cert = ReloadableCert::load(&conf).unwrap();
+        assert_eq!(current_cn(&cert), "localhost");
+        assert!(cert.acceptor().is_ok());
+        assert!(!Path::new(&conf.key_pem).exists());
+
+        write_self_signed(&dir, "real");
+        assert!(cert.reload().is_ok());
+        assert_eq!(current_cn(&cert), "real");
+    }
+}
@@ -0,0 +1,130 @@
+use super::key;
+use crate::{acme::write_key_pair, Result, SSLConf};
+use openssl::{
+    asn1::{Asn1Integer, Asn1Time},
+    bn::{BigNum, MsbOption},
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::{PKey, Private},
+    x509::{extension::SubjectAlternativeName, X509NameBuilder, X509},
+};
+use std::path::Path;
+
+/// How long a generated certificate is valid
+const VALID_DAYS: u32 = 365;
+
+/// A generated certificate and key, only meant for development
+pub struct SelfSigned {
+    /// P-256 key
+    pub key: PKey<Private>,
+    /// Certificate for `localhost`, 127.0.0.1 and ::1
+    pub cert: X509,
+}
+
+impl std::fmt::Debug for SelfSigned {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelfSigned")
+            .field("subject", &self.cert.subject_name())
+            .finish()
+    }
+}
+
+impl SelfSigned {
+    /// A certificate for `localhost` and the loopback addresses
+    pub fn localhost() -> Result<Self> {
+        Self::generate("localhost")
+    }
+
+    /// A certificate with `hostname` as common name and DNS name
+    pub fn generate(hostname: &str) -> Result<Self> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_nid(Nid::COMMONNAME, hostname)?;
+        let name = name.build();
+
+        let mut serial = BigNum::new()?;
+        serial.rand(128, MsbOption::MAYBE_ZERO, false)?;
+
+        let serial = Asn1Integer::from_bn(&serial)?;
+        let not_before = Asn1Time::days_from_now(0)?;
+        let not_after = Asn1Time::days_from_now(VALID_DAYS)?;
+
+        let mut cert = X509::builder()?;
+        cert.set_version(2)?;
+        cert.set_serial_number(&serial)?;
+        cert.set_subject_name(&name)?;
+        cert.set_issuer_name(&name)?;
+        cert.set_pubkey(&key)?;
+        cert.set_not_before(&not_before)?;
+        cert.set_not_after(&not_after)?;
+        let san = SubjectAlternativeName::new()
+            .dns(hostname)
+            .ip("127.0.0.1")
+            .ip("::1")
+            .build(&cert.x509v3_context(None, None))?;
+        cert.append_extension(san)?;
+        cert.sign(&key, MessageDigest::sha256())?;
+
+        Ok(SelfSigned {
+            key,
+            cert: cert.build(),
+        })
+    }
+
+    /// Write to `conf.cert_pem` and `conf.key_pem`, the key encrypted with
+    /// `conf.key_passphrase` when there is one
+    pub fn write(&self, conf: &SSLConf) -> Result<()> {
+        write_key_pair(
+            Path::new(&conf.key_pem),
+            &key::private_key_pem(conf, &self.key)?,
+            Path::new(&conf.cert_pem),
+            &self.cert.to_pem()?,
+        )
+    }
+}
+
+/// True when the development mode should generate a certificate:
+/// it is enabled and neither the key nor the certificate exist
+pub(super) fn wanted(conf: &SSLConf) -> bool {
+    conf.self_signed && !Path::new(&conf.key_pem).exists() && !Path::new(&conf.cert_pem).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use crate::Passphrase;
+    use std::fs;
+
+    #[test]
+    fn written_files_load_like_any_other() {
+        let dir = TestDir::new("self_signed");
+        let conf = SSLConf {
+            key_pem: dir.join("key.pem").display().to_string(),
+            cert_pem: dir.join("cert.pem").display().to_string(),
+            key_passphrase: Some(Passphrase::Value("dev".to_string().into())),
+            self_signed: true,
+            ..SSLConf::default()
+        };
+        assert!(wanted(&conf));
+
+        let generated = SelfSigned::localhost().unwrap();
+        generated.write(&conf).unwrap();
+        assert!(!wanted(&conf));
+
+        let key = key::private_key(&conf).unwrap();
+        assert!(key.public_eq(&generated.key));
+        let cert = X509::from_pem(&fs::read(&conf.cert_pem).unwrap()).unwrap();
+        assert!(cert.verify(&key).unwrap());
+        let names: Vec<String> = cert
+            .subject_alt_names()
+            .unwrap()
+            .iter()
+            .filter_map(|n| n.dnsname().map(|d| d.to_string()))
+            .collect();
+        assert_eq!(names, vec!["localhost"]);
+    }
+}
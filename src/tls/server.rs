@@ -0,0 +1,59 @@
+use super::{ClientCert, ReloadableCert};
+use crate::{config::ServerConf, Result};
+use actix_http::{body::MessageBody, Error, HttpService, Request, Response};
+use actix_rt::net::TcpStream;
+use actix_service::{map_config, IntoServiceFactory, Service, ServiceFactory};
+use actix_tls::openssl::SslStream;
+use actix_web::dev::{AppConfig, Server};
+use std::fmt;
+
+/// Start the HTTPS listeners for `factory` on every address in `conf`.
+///
+/// `HttpServer::bind_openssl` has no way to hand connection state to the
+/// application, so the listeners are built from the same parts here. Each
+/// request gets the connection's `Option<ClientCert>` in its extensions.
+/// The application sees actix's default `AppConfig`, only url generation
+/// without a Host header is affected by that. Signals are left to
+/// `shutdown::serve`.
+pub fn https_server<F, I, S, B>(
+    factory: F,
+    conf: &ServerConf,
+    cert: &ReloadableCert,
+) -> Result<Server>
+where
+    F: Fn() -> I + Send + Clone + 'static,
+    I: IntoServiceFactory<S>,
+    S: ServiceFactory<Config = AppConfig, Request = Request>,
+    S::Error: Into<Error> + 'static,
+    S::InitError: fmt::Debug,
+    S::Response: Into<Response<B>> + 'static,
+    <S::Service as Service>::Future: 'static,
+    B: MessageBody + 'static,
+{
+    let mut builder = Server::build()
+        .backlog(conf.backlog)
+        .disable_signals()
+        .shutdown_timeout(conf.shutdown_timeout);
+    if let Some(workers) = conf.workers {
+        builder = builder.workers(workers);
+    }
+
+    for addr in &conf.listen {
+        let acceptor = cert.acceptor()?.build();
+        let factory = factory.clone();
+        let (keep_alive, client_timeout, client_shutdown) =
+            (conf.keep_alive, conf.client_timeout, conf.client_shutdown);
+        builder = builder.bind(format!("rust_at_one-https-{}", addr), addr, move || {
+            HttpService::build()
+                .keep_alive(keep_alive)
+                .client_timeout(client_timeout)
+                .client_disconnect(client_shutdown)
+                .secure()
+                .on_connect(|io: &SslStream<TcpStream>| ClientCert::from_ssl(io.ssl()))
+                .finish(map_config(factory(), |_| AppConfig::default()))
+                .openssl(acceptor.clone())
+        })?;
+    }
+
+    Ok(builder.run())
+}
@@ -0,0 +1,60 @@
+use super::{self_signed_builder, ssl_builder, SelfSigned};
+use crate::{Result, SSLConf};
+use openssl::ssl::SslContext;
+use std::collections::HashMap;
+
+/// The default context and one per `SSLConf::sni` hostname
+pub(super) struct Certificates {
+    default: SslContext,
+    by_host: HashMap<String, SslContext>,
+}
+
+impl Certificates {
+    /// Build every context, failing if any certificate or key can't be used.
+    /// A `generated` certificate replaces the default files.
+    pub fn load(conf: &SSLConf, generated: Option<&SelfSigned>) -> Result<Self> {
+        let mut by_host = HashMap::new();
+        for binding in &conf.sni {
+            let bound = SSLConf {
+                cert_pem: binding.cert_pem.clone(),
+                key_pem: binding.key_pem.clone(),
+                ..conf.clone()
+            };
+            let context = ssl_builder(&bound)?.build().into_context();
+            by_host.insert(binding.hostname.clone(), context);
+        }
+
+        let default = match generated {
+            Some(generated) => self_signed_builder(conf, generated)?,
+            None => ssl_builder(conf)?,
+        };
+        Ok(Certificates {
+            default: default.build().into_context(),
+            by_host,
+        })
+    }
+
+    /// The context for the SNI `hostname`: an exact match, then a wildcard
+    /// one level up, then the default
+    pub fn for_host(&self, hostname: Option<&str>) -> &SslContext {
+        hostname
+            .map(|h| h.to_lowercase())
+            .and_then(|h| {
+                self.by_host.get(&h).or_else(|| {
+                    let (_, parent) = h.split_once('.')?;
+                    self.by_host.get(&format!("*.{}", parent))
+                })
+            })
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Every certificate and key file in `conf`, default first
+pub(super) fn files(conf: &SSLConf) -> Vec<&str> {
+    let mut files = vec![conf.cert_pem.as_str(), conf.key_pem.as_str()];
+    for binding in &conf.sni {
+        files.push(&binding.cert_pem);
+        files.push(&binding.key_pem);
+    }
+    files
+}
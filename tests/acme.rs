@@ -0,0 +1,59 @@
+//! Issuing a certificate against a local Pebble ACME server.
+//!
+//! Pebble is not started by the tests, run it with
+//! `docker run -d --name pebble -p 14000:14000 -e PEBBLE_VA_ALWAYS_VALID=1 letsencrypt/pebble`
+//! and copy its CA with `docker cp pebble:/test/certs/pebble.minica.pem .`, then
+//! `PEBBLE_CA=pebble.minica.pem cargo test --test acme -- --ignored`.
+//! `PEBBLE_DIRECTORY` overrides the directory url.
+use actix_web::{web, App, HttpServer};
+use rust_at_one::{
+    acme::Acme, handlers::configure_acme_challenge, ssl_builder, LetsEncryptConf, SSLConf,
+};
+
+#[actix_rt::test]
+#[ignore]
+async fn issue_against_pebble() {
+    let dir = std::env::temp_dir().join(format!(
+        "rust_at_one_{}_issue_against_pebble",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let le = LetsEncryptConf {
+        email: "ops@example.com".to_string(),
+        domain: "rust-at-one.example.com".to_string(),
+        directory_url: std::env::var("PEBBLE_DIRECTORY")
+            .unwrap_or_else(|_| "https://localhost:14000/dir".to_string()),
+        storage_dir: dir.display().to_string(),
+        // Pebble validates HTTP-01 on port 5002 unless PEBBLE_VA_ALWAYS_VALID is set
+        challenge_listen: "127.0.0.1:5002".parse().unwrap(),
+        renew_before_days: 30,
+        ca_file: std::env::var("PEBBLE_CA").ok(),
+    };
+    let ssl = SSLConf {
+        lets_encrypt: Some(le.clone()),
+        key_pem: dir.join("key.pem").display().to_string(),
+        cert_pem: dir.join("cert.pem").display().to_string(),
+        ..SSLConf::default()
+    };
+
+    let acme = Acme::new(&le, &ssl);
+    let challenges = web::Data::new(acme.challenges());
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(challenges.clone())
+            .configure(configure_acme_challenge)
+    })
+    .workers(1)
+    .bind(le.challenge_listen)
+    .unwrap()
+    .run();
+
+    assert!(acme.ensure_certificate().await.unwrap());
+    // Still valid, nothing to renew
+    assert!(!acme.ensure_certificate().await.unwrap());
+    assert!(ssl_builder(&ssl).is_ok());
+
+    server.stop(false).await;
+    let _ = std::fs::remove_dir_all(&dir);
+}
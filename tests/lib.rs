@@ -7,10 +7,12 @@ use actix_web::{
 use bytes::Bytes;
 use mongodb::bson::{doc, oid::ObjectId, Document};
 use rust_at_one::handlers::configure_routes;
+use rust_at_one::middleware::{ProblemDetails, RequestIds};
 use rust_at_one::mongo::Mongo;
 use rust_at_one::{AppConfig, AppEnv, AppState};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::convert::TryInto;
 
 macro_rules! map(
     { $($key:expr => $value:expr),+ } => {
@@ -24,12 +26,120 @@ macro_rules! map(
      };
 );
 
+enum ReqVerb<'a, T> {
+    Post(&'a str, T),
+    Put(&'a str, T),
+    Get(&'a str),
+    Delete(&'a str),
+}
+
+struct TestService {
+    mongo: Mongo,
+    app_state: Data<AppState>,
+    collection: String,
+    clean_up: Option<Vec<ObjectId>>,
+}
+
+impl TestService {
+    pub async fn init(collection: String) -> Self {
+        let config = AppConfig::new(AppEnv::TryFromFile("test.env")).unwrap();
+
+        let mongo = Mongo::initialize(
+            config.mongo_db_uri.expose(),
+            config.db_name.as_str(),
+            &config.mongo,
+        )
+        .await
+        .unwrap();
+
+        let app_state = AppState::new(&mongo).wrap();
+
+        TestService {
+            mongo,
+            app_state,
+            clean_up: None,
+            collection,
+        }
+    }
+
+    pub async fn insert<T>(&mut self, input: Vec<T>)
+    where
+        T: TryInto<Document> + Clone,
+        T::Error: std::fmt::Debug,
+    {
+        let d: Vec<Document> = input
+            .iter()
+            .map(|x| x.clone().try_into().unwrap())
+            .collect();
+        let result = self
+            .mongo
+            .main_db
+            .collection(&self.collection)
+            .insert_many(d, None)
+            .await
+            .unwrap();
+        self.clean_up = Some(
+            result
+                .inserted_ids
+                .iter()
+                .map(|x| x.1.as_object_id().unwrap().clone())
+                .collect(),
+        );
+    }
+
+    pub async fn clean_up(&self) {
+        if let Some(s) = &self.clean_up {
+            self.mongo
+                .main_db
+                .collection(self.collection.as_str())
+                .delete_many(doc! {"_id": {"$in": s}}, None)
+                .await
+                .unwrap();
+        }
+    }
+
+    pub async fn make_req<In, Out>(&self, req_verb: ReqVerb<'_, In>) -> (Out, StatusCode)
+    where
+        In: Serialize + Into<web::Bytes>,
+        Out: DeserializeOwned,
+    {
+        let mut app = init_service(
+            App::new()
+                .wrap(ProblemDetails)
+                .wrap(RequestIds)
+                .app_data(self.app_state.clone())
+                .service(web::scope("/api").configure(configure_routes)),
+        )
+        .await;
+
+        let req = match req_verb {
+            ReqVerb::Post(p, v) => test::TestRequest::post().uri(p).set_payload(v),
+            ReqVerb::Put(p, v) => test::TestRequest::put().uri(p).set_payload(v),
+            ReqVerb::Get(p) => test::TestRequest::get().uri(p),
+            ReqVerb::Delete(p) => test::TestRequest::delete().uri(p),
+        };
+        let req = req
+            .header(header::CONTENT_TYPE, "application/json")
+            .to_request();
+
+        let response = test::call_service(&mut app, req).await;
+        let status = response.status();
+        let m: Bytes = test::read_body(response).await;
+        // responses without a body, like a successful DELETE, read as null
+        let m = if m.is_empty() { Bytes::from("null") } else { m };
+        let model: Out = serde_json::from_str(std::str::from_utf8(&m).unwrap()).unwrap();
+
+        (model, status)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{ReqVerb, TestService};
     use actix_web::http::StatusCode;
     use rstest::*;
     use rust_at_one::documents::Post;
+    use serde_json::Value;
     use std::collections::HashMap;
     use uuid::Uuid;
 
@@ -101,99 +211,64 @@ mod tests {
         assert_eq!(resp.0.len(), count);
         assert_eq!(resp.1, 200);
     }
-}
-
-enum ReqVerb<'a, T> {
-    Post(&'a str, T),
-    Put(&'a str, T),
-    Get(&'a str),
-    Delete(&'a str),
-}
 
-struct TestService {
-    mongo: Mongo,
-    app_state: Data<AppState>,
-    collection: String,
-    clean_up: Option<Vec<ObjectId>>,
-}
+    #[actix_rt::test]
+    async fn valid_id_round_trip() {
+        let service = TestService::init("post".to_string()).await;
+        let author = Uuid::new_v4().to_string();
+        let body = format!("{{\"name\":\"Created\",\"author\":\"{}\"}}", author);
+        let (created, status): (Post, StatusCode) =
+            service.make_req(ReqVerb::Post("/api/posts", body)).await;
+        assert_eq!(status, 201);
+        let url = format!("/api/posts/{}", created.id.unwrap());
 
-impl TestService {
-    pub async fn init(collection: String) -> Self {
-        let config = AppConfig::new(AppEnv::TryFromFile("test.env"));
+        let (found, status): (Post, StatusCode) =
+            service.make_req(ReqVerb::Get::<String>(url.as_str())).await;
+        assert_eq!(status, 200);
+        assert_eq!(found.author.as_deref(), Some(author.as_str()));
 
-        let mongo = Mongo::initialize(config.mongo_db_uri.as_str(), config.db_name.as_str())
-            .await
-            .unwrap();
-
-        let app_state = AppState::new(&mongo).wrap();
+        let body = "{\"name\":\"Changed\"}".to_string();
+        let (changed, status): (Post, StatusCode) =
+            service.make_req(ReqVerb::Put(url.as_str(), body)).await;
+        assert_eq!(status, 200);
+        assert_eq!(changed.name.as_deref(), Some("Changed"));
 
-        TestService {
-            mongo,
-            app_state,
-            clean_up: None,
-            collection,
-        }
-    }
-
-    pub async fn insert<T>(&mut self, input: Vec<T>)
-    where
-        T: Into<Document> + Clone,
-    {
-        let d: Vec<Document> = input.iter().map(|x| x.clone().into()).collect();
-        let result = self
-            .mongo
-            .main_db
-            .collection(&self.collection)
-            .insert_many(d, None)
-            .await
-            .unwrap();
-        self.clean_up = Some(
-            result
-                .inserted_ids
-                .iter()
-                .map(|x| x.1.as_object_id().unwrap().clone())
-                .collect(),
-        );
-    }
-
-    pub async fn clean_up(&self) {
-        if let Some(s) = &self.clean_up {
-            self.mongo
-                .main_db
-                .collection(self.collection.as_str())
-                .delete_many(doc! {"_id": {"$in": s}}, None)
-                .await
-                .unwrap();
-        }
+        let (_, status): (Value, StatusCode) = service
+            .make_req(ReqVerb::Delete::<String>(url.as_str()))
+            .await;
+        assert_eq!(status, 200);
     }
 
-    pub async fn make_req<In, Out>(&self, req_verb: ReqVerb<'_, In>) -> (Out, StatusCode)
-    where
-        In: Serialize + Into<web::Bytes>,
-        Out: DeserializeOwned,
-    {
-        let mut app = init_service(
-            App::new()
-                .app_data(self.app_state.clone())
-                .service(web::scope("/api").configure(configure_routes)),
-        )
-        .await;
+    #[rstest(
+        verb,
+        case(ReqVerb::Get("/api/posts/not-an-id")),
+        case(ReqVerb::Put("/api/posts/not-an-id", "{\"name\":\"Changed\"}".to_string())),
+        case(ReqVerb::Delete("/api/posts/not-an-id")),
+        case(ReqVerb::Get("/api/posts?id=not-an-id"))
+    )]
+    #[actix_rt::test]
+    async fn invalid_id_is_rejected(verb: ReqVerb<'static, String>) {
+        let mut service = TestService::init("post".to_string()).await;
+        let author = Uuid::new_v4().to_string();
+        service
+            .insert(vec![Post {
+                id: None,
+                name: Some("Untouched".to_string()),
+                author: Some(author.clone()),
+            }])
+            .await;
 
-        let req = match req_verb {
-            ReqVerb::Post(p, v) => test::TestRequest::post().uri(p).set_payload(v),
-            ReqVerb::Put(p, v) => test::TestRequest::put().uri(p).set_payload(v),
-            ReqVerb::Get(p) => test::TestRequest::get().uri(p),
-            ReqVerb::Delete(p) => test::TestRequest::delete().uri(p),
-        };
-        let req = req
-            .header(header::CONTENT_TYPE, "application/json")
-            .to_request();
+        let resp: (Value, StatusCode) = service.make_req(verb).await;
 
-        let response = test::call_service(&mut app, req).await;
-        let status = response.status().clone();
-        let m: Bytes = test::read_body(response).await;
-        let model: Out = serde_json::from_str(std::str::from_utf8(&m).unwrap()).unwrap();
+        let url = format!("/api/posts?author={}", author);
+        let after: (Vec<Post>, StatusCode) =
+            service.make_req(ReqVerb::Get::<String>(url.as_str())).await;
+        service.clean_up().await;
 
-        (model, status)
+        assert_eq!(resp.1, 400);
+        assert_eq!(resp.0["code"], "bad_request");
+        assert_eq!(resp.0["detail"], "'not-an-id' is not a valid id");
+        assert_eq!(after.0.len(), 1);
+        assert_eq!(after.0[0].name.as_deref(), Some("Untouched"));
     }
 }